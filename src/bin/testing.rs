@@ -1,21 +1,9 @@
-use std::io;
-use recursion::Collapsible;
 use computation_graph::graph::node_frame::NodeFrame;
 use computation_graph::graph::boxed_nodes::BoxedNode;
 use computation_graph::graph::node_frame::BoolOp;
 use computation_graph::graph::node_transformer::NodeTransformer;
 use computation_graph::operations::BooleanOperation;
-
-macro_rules! bool_op {
-        ($operator:expr, $($op:expr),*) => {
-            BoxedNode {
-                data: NodeFrame::BoolOp(BoolOp {
-                    operator: $operator,
-                    operands: vec![$(Box::new($op),)*]
-                })
-            }
-        };
-    }
+use computation_graph::parser::parse;
 
 macro_rules! bool_const {
         ($val:expr) => {
@@ -69,13 +57,7 @@ impl NodeTransformer for Ugh {
 }
 
 fn main() {
-    let macro_graph: BoxedNode = bool_op!(BooleanOperation::And,
-        bool_const!(true),
-        bool_op!(BooleanOperation::Or,
-            bool_const!(false),
-            bool_const!(false)
-        )
-    );
+    let macro_graph: BoxedNode = parse("True and (False or False)").unwrap();
     
     println!("Hello, world!");
     let mut ug = Ugh {};
@@ -1,7 +1,12 @@
-use std::cmp::PartialEq;
+pub mod annotation;
+pub mod boundary;
+pub mod partition;
+pub mod signature;
+
+use crate::computing::signature::ParameterSignature;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
-pub(crate) enum ComputingDomain {
+pub enum ComputingDomain {
     Classical,
     Quantum,
     Conflict,
@@ -9,45 +14,142 @@ pub(crate) enum ComputingDomain {
 }
 
 
-pub(crate) trait Computable {
-    fn get_domain(&self) -> ComputingDomain;
+pub trait Computable {
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain;
 }
 
 impl<T: Computable> Computable for Vec<T> {
-    fn get_domain(&self) -> ComputingDomain {
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
        self.iter()
-           .map(|x| x.get_domain())
+           .map(|x| x.get_domain(signature))
            .fold(ComputingDomain::Unknown, |a, b| a.compare(&b))
     }
 }
 
+// Lets a frame whose children were already reduced to their domains compute its own domain
+impl Computable for ComputingDomain {
+    fn get_domain(&self, _: &ParameterSignature) -> ComputingDomain {
+        *self
+    }
+}
+
 impl<T: Computable> Computable for Box<T> {
-    fn get_domain(&self) -> ComputingDomain {
-        self.as_ref().get_domain()
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        self.as_ref().get_domain(signature)
     }
 }
 
 
+// The domains form a join-semilattice:
+// Unknown is the bottom and joins to whatever it meets, Conflict is the top and absorbs everything,
+// and Classical and Quantum are only compatible with themselves
 impl ComputingDomain {
-    pub(crate) fn compare(&self, other: &Self) -> Self {
-        if let ComputingDomain::Conflict = self {
-            return ComputingDomain::Conflict;
+    pub fn compare(&self, other: &Self) -> Self {
+        match (self, other) {
+            (ComputingDomain::Conflict, _) | (_, ComputingDomain::Conflict) => ComputingDomain::Conflict,
+            (ComputingDomain::Unknown, domain) | (domain, ComputingDomain::Unknown) => *domain,
+            (left, right) if left == right => *left,
+            _ => ComputingDomain::Conflict,
         }
-        if let ComputingDomain::Conflict = other {
-            return ComputingDomain::Conflict;
+    }
+}
+
+#[cfg(test)]
+mod computing_tests {
+    use super::*;
+    use crate::computing::signature::ParameterType;
+    use crate::parser::parse;
+
+    fn signature() -> ParameterSignature {
+        ["x", "y", "z"].into_iter().fold(ParameterSignature::new(), |signature, name| {
+            signature.with_parameter(name, ComputingDomain::Quantum, ParameterType::BitVec(2))
+        })
+    }
+
+    const DOMAINS: [ComputingDomain; 4] = [
+        ComputingDomain::Classical,
+        ComputingDomain::Quantum,
+        ComputingDomain::Conflict,
+        ComputingDomain::Unknown,
+    ];
+
+    #[test]
+    fn join_is_commutative() {
+        for a in DOMAINS {
+            for b in DOMAINS {
+                assert_eq!(a.compare(&b), b.compare(&a), "{a:?} and {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn join_is_associative() {
+        for a in DOMAINS {
+            for b in DOMAINS {
+                for c in DOMAINS {
+                    assert_eq!(a.compare(&b).compare(&c), a.compare(&b.compare(&c)), "{a:?}, {b:?} and {c:?}");
+                }
+            }
         }
-        if self != other {
-            return ComputingDomain::Conflict;
+    }
+
+    #[test]
+    fn join_is_idempotent() {
+        for a in DOMAINS {
+            assert_eq!(a, a.compare(&a));
         }
+    }
 
-        if let ComputingDomain::Unknown = self {
-            return *other;
+    #[test]
+    fn unknown_is_bottom() {
+        for a in DOMAINS {
+            assert_eq!(a, ComputingDomain::Unknown.compare(&a));
         }
-        if let ComputingDomain::Unknown = other {
-            return *self;
+    }
+
+    #[test]
+    fn conflict_is_top() {
+        for a in DOMAINS {
+            assert_eq!(ComputingDomain::Conflict, ComputingDomain::Conflict.compare(&a));
         }
+    }
 
-        // self should equal other, so it doesn't matter which we return a copy of
-        *self
+    #[test]
+    fn classical_and_quantum_conflict() {
+        assert_eq!(ComputingDomain::Conflict, ComputingDomain::Classical.compare(&ComputingDomain::Quantum));
+    }
+
+    #[test]
+    fn empty_operand_lists_are_unknown() {
+        let operands: Vec<ComputingDomain> = Vec::new();
+        assert_eq!(ComputingDomain::Unknown, operands.get_domain(&ParameterSignature::new()));
+    }
+
+    #[test]
+    fn boolean_operations_take_the_domain_of_their_operands() -> anyhow::Result<()> {
+        let signature = signature();
+        assert_eq!(ComputingDomain::Classical, parse("True and False or True")?.get_domain(&signature));
+        assert_eq!(ComputingDomain::Quantum, parse("x and y and z")?.get_domain(&signature));
+        assert_eq!(ComputingDomain::Conflict, parse("x and True")?.get_domain(&signature));
+        Ok(())
+    }
+
+    #[test]
+    fn comparisons_take_the_domain_of_their_operands() -> anyhow::Result<()> {
+        let signature = signature();
+        assert_eq!(ComputingDomain::Classical, parse("1 < 2 <= 3")?.get_domain(&signature));
+        assert_eq!(ComputingDomain::Quantum, parse("x < y == z")?.get_domain(&signature));
+        assert_eq!(ComputingDomain::Conflict, parse("x < 2")?.get_domain(&signature));
+        Ok(())
+    }
+
+    #[test]
+    fn parameter_domains_are_looked_up_in_the_signature() -> anyhow::Result<()> {
+        let signature = signature().with_parameter("i", ComputingDomain::Classical, ParameterType::Int);
+        assert_eq!(ComputingDomain::Classical, parse("i + 1")?.get_domain(&signature));
+        assert_eq!(ComputingDomain::Conflict, parse("i + x")?.get_domain(&signature));
+        // Undeclared parameters add nothing, ParameterSignature::domain_of reports them
+        assert_eq!(ComputingDomain::Classical, parse("i + w")?.get_domain(&signature));
+        Ok(())
     }
 }
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use crate::computing::{Computable, ComputingDomain};
+use crate::computing::signature::{ParameterSignature, UndeclaredParameter};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::NodeFrame;
+use crate::graph::node_path::{collapse_with_paths, NodePath, PathFrame};
+
+#[derive(Debug, Clone)]
+pub struct DomainAnnotations {
+    domains: HashMap<NodePath, ComputingDomain>,
+    conflicts: Vec<ConflictSite>,
+}
+
+// A node whose operands are free of conflicts on their own, but cannot be combined
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictSite {
+    pub path: NodePath,
+    pub operands: Vec<(NodePath, ComputingDomain)>,
+}
+
+impl DomainAnnotations {
+    pub fn domain_of(&self, path: &NodePath) -> Option<ComputingDomain> {
+        self.domains.get(path).copied()
+    }
+
+    pub fn domains(&self) -> &HashMap<NodePath, ComputingDomain> {
+        &self.domains
+    }
+
+    // The lowest nodes where Conflict arises, every other conflicting node has one of them as a descendant
+    pub fn conflicts(&self) -> &[ConflictSite] {
+        &self.conflicts
+    }
+}
+
+// Computes the domain of every node in a single bottom-up traversal, with parameter domains taken from the signature
+pub fn annotate_domains(node: &BoxedNode, signature: &ParameterSignature) -> Result<DomainAnnotations, UndeclaredParameter> {
+    let mut domains = HashMap::new();
+    let mut conflicts = Vec::new();
+
+    collapse_with_paths(node, |PathFrame { path, frame }: PathFrame<Result<ComputingDomain, UndeclaredParameter>>| {
+        let frame = frame.try_map(|child| child)?;
+        let domain = match &frame {
+            NodeFrame::FunctionParameter(parameter) => signature.lookup(&parameter.identifier, &path)?,
+            other => other.get_domain(signature),
+        };
+        let operands = frame.children();
+        if domain == ComputingDomain::Conflict && !operands.contains(&&ComputingDomain::Conflict) {
+            conflicts.push(ConflictSite {
+                operands: operands.into_iter().enumerate().map(|(index, domain)| (path.child(index), *domain)).collect(),
+                path: path.clone(),
+            });
+        }
+        domains.insert(path, domain);
+        Ok(domain)
+    })?;
+
+    conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(DomainAnnotations {
+        domains,
+        conflicts,
+    })
+}
+
+#[cfg(test)]
+mod annotation_tests {
+    use super::*;
+    use crate::computing::signature::ParameterType;
+    use crate::parser::parse;
+
+    fn path(indices: &[usize]) -> NodePath {
+        NodePath::from_indices(indices.to_vec())
+    }
+
+    fn signature() -> ParameterSignature {
+        ["a", "b", "q", "x", "y", "z"].into_iter().fold(ParameterSignature::new(), |signature, name| {
+            signature.with_parameter(name, ComputingDomain::Quantum, ParameterType::BitVec(2))
+        })
+    }
+
+    #[test]
+    fn every_node_is_annotated() -> anyhow::Result<()> {
+        let graph = parse("(x ^ y) + -(1 * 2)")?;
+        let annotations = annotate_domains(&graph, &signature())?;
+
+        assert_eq!(8, annotations.domains().len());
+        assert_eq!(Some(ComputingDomain::Quantum), annotations.domain_of(&path(&[0])));
+        assert_eq!(Some(ComputingDomain::Quantum), annotations.domain_of(&path(&[0, 1])));
+        assert_eq!(Some(ComputingDomain::Classical), annotations.domain_of(&path(&[1])));
+        assert_eq!(Some(ComputingDomain::Classical), annotations.domain_of(&path(&[1, 0, 1])));
+        assert_eq!(Some(ComputingDomain::Conflict), annotations.domain_of(&NodePath::root()));
+        assert_eq!(None, annotations.domain_of(&path(&[2])));
+        Ok(())
+    }
+
+    #[test]
+    fn annotations_agree_with_get_domain() -> anyhow::Result<()> {
+        let signature = signature().with_parameter("x", ComputingDomain::Classical, ParameterType::Int);
+        let graph = parse("a if x < 3 and not y else (b | 0b01) + 2")?;
+        let annotations = annotate_domains(&graph, &signature)?;
+        for (path, domain) in annotations.domains() {
+            let node = path.resolve(&graph).expect("annotated paths exist");
+            assert_eq!(node.get_domain(&signature), *domain, "at {path}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn conflicts_are_reported_where_they_arise() -> anyhow::Result<()> {
+        let graph = parse("(x + 1) * (y & z) - (2 < q)")?;
+        let annotations = annotate_domains(&graph, &signature())?;
+
+        let expected = vec![
+            ConflictSite {
+                path: path(&[0, 0]),
+                operands: vec![(path(&[0, 0, 0]), ComputingDomain::Quantum), (path(&[0, 0, 1]), ComputingDomain::Classical)],
+            },
+            ConflictSite {
+                path: path(&[1]),
+                operands: vec![(path(&[1, 0]), ComputingDomain::Classical), (path(&[1, 1]), ComputingDomain::Quantum)],
+            },
+        ];
+        assert_eq!(expected, annotations.conflicts());
+        Ok(())
+    }
+
+    #[test]
+    fn signatures_drive_parameter_domains() -> anyhow::Result<()> {
+        let signature = ParameterSignature::new()
+            .with_parameter("n", ComputingDomain::Classical, ParameterType::Int)
+            .with_parameter("q", ComputingDomain::Quantum, ParameterType::BitVec(8));
+        let annotations = annotate_domains(&parse("(n - 1) & q")?, &signature)?;
+
+        assert_eq!(Some(ComputingDomain::Classical), annotations.domain_of(&path(&[0])));
+        assert_eq!(vec![NodePath::root()], annotations.conflicts().iter().map(|site| site.path.clone()).collect::<Vec<_>>());
+
+        assert!(annotate_domains(&parse("n + m")?, &signature).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn graphs_without_conflicts_report_none() -> anyhow::Result<()> {
+        assert!(annotate_domains(&parse("x & y | z")?, &signature())?.conflicts().is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::computing::{Computable, ComputingDomain};
+use crate::computing::signature::{ParameterSignature, UndeclaredParameter};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{Encode, Measure, NodeFrame};
+use crate::graph::node_path::{collapse_with_paths, PathFrame};
+
+// Which side of a conflict crosses the boundary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictResolution {
+    // Classical operands are encoded, the conflicting operation runs on quantum data
+    #[default]
+    Encode,
+    // Quantum operands are measured, the conflicting operation runs on classical data
+    Measure,
+}
+
+// Wraps the operands of every conflicting node in Measure or Encode, so the result has no Conflict left.
+// Parameter domains are taken from the signature
+pub fn insert_boundaries(node: &BoxedNode, signature: &ParameterSignature, resolution: ConflictResolution) -> Result<BoxedNode, UndeclaredParameter> {
+    let crossing = match resolution {
+        ConflictResolution::Encode => ComputingDomain::Classical,
+        ConflictResolution::Measure => ComputingDomain::Quantum,
+    };
+
+    let (node, _) = collapse_with_paths(node, |PathFrame { path, frame }: PathFrame<Result<(BoxedNode, ComputingDomain), UndeclaredParameter>>| {
+        let frame = frame.try_map(|child| child)?;
+        if let NodeFrame::FunctionParameter(parameter) = &frame {
+            let domain = signature.lookup(&parameter.identifier, &path)?;
+            return Ok((BoxedNode { data: frame.map(|(child, _)| Box::new(child)) }, domain));
+        }
+
+        let conflicting = frame.children().into_iter().map(|(_, domain)| *domain).collect::<Vec<_>>().get_domain(signature) == ComputingDomain::Conflict;
+        let frame = frame.map(|(child, domain)| {
+            if conflicting && domain == crossing {
+                cross(child, resolution)
+            } else {
+                (child, domain)
+            }
+        });
+        let domain = frame.as_ref().map(|(_, domain)| *domain).get_domain(signature);
+        Ok((BoxedNode { data: frame.map(|(child, _)| Box::new(child)) }, domain))
+    })?;
+    Ok(node)
+}
+
+fn cross(node: BoxedNode, resolution: ConflictResolution) -> (BoxedNode, ComputingDomain) {
+    let operand = Box::new(node);
+    match resolution {
+        ConflictResolution::Encode => (BoxedNode { data: NodeFrame::Encode(Encode { operand }) }, ComputingDomain::Quantum),
+        ConflictResolution::Measure => (BoxedNode { data: NodeFrame::Measure(Measure { operand }) }, ComputingDomain::Classical),
+    }
+}
+
+#[cfg(test)]
+mod boundary_tests {
+    use super::*;
+    use crate::computing::annotation::annotate_domains;
+    use crate::computing::signature::ParameterType;
+    use crate::parser::parse;
+
+    fn signature() -> ParameterSignature {
+        ParameterSignature::new()
+            .with_parameter("n", ComputingDomain::Classical, ParameterType::Int)
+            .with_parameter("q", ComputingDomain::Quantum, ParameterType::BitVec(4))
+    }
+
+    #[test]
+    fn classical_operands_are_encoded() -> anyhow::Result<()> {
+        let graph = parse("(q ^ n + 1) & q")?;
+        let resolved = insert_boundaries(&graph, &signature(), ConflictResolution::Encode)?;
+        assert_eq!("(q ^ encode(n + 1)) & q", resolved.to_string());
+        assert_eq!(ComputingDomain::Quantum, signature().domain_of(&resolved)?);
+        Ok(())
+    }
+
+    #[test]
+    fn quantum_operands_are_measured() -> anyhow::Result<()> {
+        let graph = parse("n + 1 if q > 3 else n")?;
+        let resolved = insert_boundaries(&graph, &signature(), ConflictResolution::Measure)?;
+        assert_eq!("n + 1 if measure(q) > 3 else n", resolved.to_string());
+        assert!(annotate_domains(&resolved, &signature())?.conflicts().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn graphs_without_conflicts_are_unchanged() -> anyhow::Result<()> {
+        let graph = parse("q & ~(q ^ q)")?;
+        let resolved = insert_boundaries(&graph, &signature(), ConflictResolution::Encode)?;
+        assert_eq!(graph.get_structure_key(), resolved.get_structure_key());
+        Ok(())
+    }
+
+    #[test]
+    fn boundaries_fix_the_domain_of_their_operand() -> anyhow::Result<()> {
+        let signature = signature();
+        assert_eq!(ComputingDomain::Classical, parse("measure(q)")?.get_domain(&signature));
+        assert_eq!(ComputingDomain::Quantum, parse("encode(1 + 2)")?.get_domain(&signature));
+        assert_eq!(ComputingDomain::Classical, parse("measure(q) + 1")?.get_domain(&signature));
+        assert_eq!(ComputingDomain::Conflict, parse("encode(q + 1)")?.get_domain(&signature));
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use crate::computing::ComputingDomain;
+use crate::computing::annotation::{annotate_domains, DomainAnnotations};
+use crate::computing::signature::{ParameterSignature, ParameterTypeError, UndeclaredParameter};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::fresh_names::FreshNames;
+use crate::graph::node_frame::{FunctionParameter, NodeFrame};
+use crate::graph::node_path::{collapse_with_paths, NodePath, PathFrame};
+
+// A classical expression lifted out of the graph, to be computed before the rest of it
+#[derive(Debug, Clone)]
+pub struct ExtractedExpression {
+    // The fresh parameter that replaced the expression
+    pub parameter: String,
+    // Where the expression was in the original graph
+    pub path: NodePath,
+    pub expression: BoxedNode,
+}
+
+#[derive(Debug, Clone)]
+pub struct Partition {
+    // The original graph with every extracted expression replaced by its parameter
+    pub remainder: BoxedNode,
+    // Ordered by path
+    pub classical: Vec<ExtractedExpression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PartitionError {
+    UndeclaredParameter(UndeclaredParameter),
+    // The extracted expression's type cannot be known statically, so its parameter cannot be declared
+    UnknownType { parameter: String, path: NodePath },
+    InvalidType(ParameterTypeError),
+}
+
+impl Display for PartitionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PartitionError::UndeclaredParameter(error) => write!(f, "{error}"),
+            PartitionError::UnknownType { parameter, path } => {
+                write!(f, "the type of the expression at {path} extracted as `{parameter}` is not known")
+            }
+            PartitionError::InvalidType(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for PartitionError {}
+
+impl From<UndeclaredParameter> for PartitionError {
+    fn from(error: UndeclaredParameter) -> Self {
+        PartitionError::UndeclaredParameter(error)
+    }
+}
+
+// Extracts every maximal classical subtree, with parameter domains taken from the signature.
+// Fresh parameters never reuse a name the signature declares, and the returned signature extends the given one
+// with every fresh parameter as Classical, typed like its expression, so the remainder can be classified again
+pub fn partition(node: &BoxedNode, signature: &ParameterSignature) -> Result<(Partition, ParameterSignature), PartitionError> {
+    let annotations = annotate_domains(node, signature)?;
+    let declared = signature.parameters().iter().map(|declaration| declaration.name.clone()).collect();
+    let partition = split(node, &annotations, declared);
+
+    let mut extended = signature.clone();
+    for extracted in &partition.classical {
+        let parameter_type = signature.type_of(&extracted.expression)
+            .map_err(|error| {
+                let indices = extracted.path.indices().iter().chain(error.path.indices()).copied().collect();
+                PartitionError::InvalidType(ParameterTypeError {
+                    path: NodePath::from_indices(indices),
+                    ..error
+                })
+            })?
+            .ok_or_else(|| PartitionError::UnknownType {
+                parameter: extracted.parameter.clone(),
+                path: extracted.path.clone(),
+            })?;
+        extended.declare(extracted.parameter.clone(), ComputingDomain::Classical, parameter_type);
+    }
+    Ok((partition, extended))
+}
+
+// A classical subtree is extracted when its parent is not classical itself.
+// Leaves are left in place, replacing a constant or a parameter with a parameter gains nothing
+fn split(node: &BoxedNode, annotations: &DomainAnnotations, reserved: BTreeSet<String>) -> Partition {
+    let is_classical = |path: &NodePath| annotations.domain_of(path) == Some(ComputingDomain::Classical);
+    let mut roots: Vec<&NodePath> = annotations.domains().keys()
+        .filter(|path| is_classical(path))
+        .filter(|path| path.parent().is_none_or(|parent| !is_classical(&parent)))
+        .filter(|path| path.resolve(node).is_some_and(|subtree| !subtree.data.children().is_empty()))
+        .collect();
+    roots.sort();
+
+    let mut taken = node.parameter_names();
+    taken.extend(reserved);
+    let mut names: HashMap<NodePath, String> = roots.into_iter().cloned()
+        .zip(FreshNames::new("classical", taken))
+        .collect();
+
+    let mut classical = Vec::new();
+    let remainder = collapse_with_paths(node, |PathFrame { path, frame }: PathFrame<BoxedNode>| {
+        let rebuilt = BoxedNode { data: frame.map(Box::new) };
+        match names.remove(&path) {
+            Some(parameter) => {
+                classical.push(ExtractedExpression {
+                    parameter: parameter.clone(),
+                    path,
+                    expression: rebuilt,
+                });
+                BoxedNode { data: NodeFrame::FunctionParameter(FunctionParameter { identifier: parameter }) }
+            }
+            None => rebuilt,
+        }
+    });
+
+    classical.sort_by(|a, b| a.path.cmp(&b.path));
+    Partition {
+        remainder,
+        classical,
+    }
+}
+
+#[cfg(test)]
+mod partition_tests {
+    use super::*;
+    use crate::computing::signature::ParameterType;
+    use crate::parser::parse;
+
+    fn signature() -> ParameterSignature {
+        ParameterSignature::new()
+            .with_parameter("n", ComputingDomain::Classical, ParameterType::Int)
+            .with_parameter("q", ComputingDomain::Quantum, ParameterType::BitVec(4))
+    }
+
+    fn extracted(partition: &Partition) -> Vec<(String, String)> {
+        partition.classical.iter()
+            .map(|extracted| (extracted.parameter.clone(), extracted.expression.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn maximal_classical_subtrees_are_extracted() -> anyhow::Result<()> {
+        let (partition, _) = partition(&parse("(n + 1) << (q & n * n - 1)")?, &signature())?;
+        assert_eq!("classical_0 << (q & classical_1)", partition.remainder.to_string());
+        assert_eq!(vec![
+            ("classical_0".to_string(), "n + 1".to_string()),
+            ("classical_1".to_string(), "n * n - 1".to_string()),
+        ], extracted(&partition));
+        assert_eq!(NodePath::from_indices(vec![1, 1]), partition.classical[1].path);
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_stay_in_place() -> anyhow::Result<()> {
+        let (partition, _) = partition(&parse("q + n - 1")?, &signature())?;
+        assert_eq!("q + n - 1", partition.remainder.to_string());
+        assert!(partition.classical.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn fresh_parameters_avoid_existing_names() -> anyhow::Result<()> {
+        let signature = signature()
+            .with_parameter("classical_0", ComputingDomain::Quantum, ParameterType::Int)
+            .with_parameter("classical_1", ComputingDomain::Quantum, ParameterType::Int);
+        let (partition, _) = partition(&parse("classical_0 ^ (q | 2 * 3)")?, &signature)?;
+        assert_eq!(vec![("classical_2".to_string(), "2 * 3".to_string())], extracted(&partition));
+        Ok(())
+    }
+
+    #[test]
+    fn quantum_graphs_only_extract_constant_expressions() -> anyhow::Result<()> {
+        let (partition, _) = partition(&parse("q + (1 - 2) if 3 > 4 else q")?, &signature())?;
+        // The condition comes first in path order
+        assert_eq!("q + classical_1 if classical_0 else q", partition.remainder.to_string());
+        assert_eq!(2, partition.classical.len());
+        Ok(())
+    }
+
+    #[test]
+    fn fresh_parameters_are_declared_classical() -> anyhow::Result<()> {
+        let signature = signature().with_parameter("a", ComputingDomain::Classical, ParameterType::Int);
+        let (partition, extended) = partition(&parse("q + a * 2")?, &signature)?;
+        assert_eq!("q + classical_0", partition.remainder.to_string());
+        let declaration = extended.get("classical_0").expect("the fresh parameter is declared");
+        assert_eq!((ComputingDomain::Classical, ParameterType::Int), (declaration.domain, declaration.parameter_type));
+
+        let annotations = annotate_domains(&partition.remainder, &extended)?;
+        assert_eq!(Some(ComputingDomain::Classical), annotations.domain_of(&NodePath::from_indices(vec![1])));
+        assert_eq!(Some(ComputingDomain::Conflict), annotations.domain_of(&NodePath::root()));
+        Ok(())
+    }
+
+    #[test]
+    fn untyped_expressions_are_reported() -> anyhow::Result<()> {
+        let signature = signature()
+            .with_parameter("s", ComputingDomain::Classical, ParameterType::String)
+            .with_parameter("c", ComputingDomain::Classical, ParameterType::Bool);
+        let error = partition(&parse("q & (n if c else s)")?, &signature).expect_err("the branches differ");
+        assert_eq!(PartitionError::UnknownType { parameter: "classical_0".to_string(), path: NodePath::from_indices(vec![1]) }, error);
+
+        let error = partition(&parse("q & (n + (s - 1))")?, &signature).expect_err("strings cannot be subtracted");
+        assert!(matches!(error, PartitionError::InvalidType(error) if error.path == NodePath::from_indices(vec![1, 1])));
+        Ok(())
+    }
+
+    #[test]
+    fn undeclared_parameters_are_reported() -> anyhow::Result<()> {
+        assert!(partition(&parse("q + m")?, &signature()).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use std::fmt::{Display, Formatter};
+use crate::computing::{Computable, ComputingDomain};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{NodeFrame, Numeric};
+use crate::graph::node_path::{collapse_with_paths, NodePath, PathFrame};
+use crate::operations::{BinaryOperation, ComparisonOperation, UnaryOperation};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParameterType {
+    Int,
+    Double,
+    Bool,
+    String,
+    // Register width in bits
+    BitVec(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ParameterDeclaration {
+    pub name: String,
+    pub domain: ComputingDomain,
+    pub parameter_type: ParameterType,
+}
+
+// The parameters a graph takes, in declaration order.
+// Every computation of a domain takes one, it is where FunctionParameters get their domain from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParameterSignature {
+    parameters: Vec<ParameterDeclaration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndeclaredParameter {
+    pub name: String,
+    // Path of the first FunctionParameter node using the name
+    pub path: NodePath,
+}
+
+impl Display for UndeclaredParameter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "parameter `{}` at {} is not declared", self.name, self.path)
+    }
+}
+
+impl std::error::Error for UndeclaredParameter {}
+
+// An operation the declared parameter types make invalid, e.g. `s - 1` for a String s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParameterTypeError {
+    pub description: String,
+    pub path: NodePath,
+}
+
+impl Display for ParameterTypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "type error at {}: {}", self.path, self.description)
+    }
+}
+
+impl std::error::Error for ParameterTypeError {}
+
+impl ParameterSignature {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_parameter(mut self, name: impl Into<String>, domain: ComputingDomain, parameter_type: ParameterType) -> Self {
+        self.declare(name, domain, parameter_type);
+        self
+    }
+
+    // Declaring a name again replaces its previous declaration
+    pub fn declare(&mut self, name: impl Into<String>, domain: ComputingDomain, parameter_type: ParameterType) {
+        let declaration = ParameterDeclaration {
+            name: name.into(),
+            domain,
+            parameter_type,
+        };
+        match self.parameters.iter_mut().find(|existing| existing.name == declaration.name) {
+            Some(existing) => *existing = declaration,
+            None => self.parameters.push(declaration),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ParameterDeclaration> {
+        self.parameters.iter().find(|declaration| declaration.name == name)
+    }
+
+    pub fn parameters(&self) -> &[ParameterDeclaration] {
+        &self.parameters
+    }
+
+    pub(crate) fn lookup(&self, name: &str, path: &NodePath) -> Result<ComputingDomain, UndeclaredParameter> {
+        self.get(name)
+            .map(|declaration| declaration.domain)
+            .ok_or_else(|| UndeclaredParameter {
+                name: name.to_string(),
+                path: path.clone(),
+            })
+    }
+
+    // Domain of the whole graph, with parameter domains taken from this signature
+    pub fn domain_of(&self, node: &BoxedNode) -> Result<ComputingDomain, UndeclaredParameter> {
+        collapse_with_paths(node, |PathFrame { path, frame }: PathFrame<Result<ComputingDomain, UndeclaredParameter>>| {
+            match frame.try_map(|child| child)? {
+                NodeFrame::FunctionParameter(parameter) => self.lookup(&parameter.identifier, &path),
+                other => Ok(other.get_domain(self)),
+            }
+        })
+    }
+
+    // Type of the graph's value given the declared parameter types, None when it cannot be known statically,
+    // e.g. because an undeclared parameter is involved or an If's branches have different types.
+    // Operations whose operand types are known but invalid are reported, also inside branches that may never run
+    pub fn type_of(&self, node: &BoxedNode) -> Result<Option<ParameterType>, ParameterTypeError> {
+        collapse_with_paths(node, |PathFrame { path, frame }: PathFrame<Result<Option<ParameterType>, ParameterTypeError>>| {
+            let frame = frame.try_map(|child| child)?;
+            infer_type(self, frame).map_err(|description| ParameterTypeError { description, path })
+        })
+    }
+}
+
+// Python's result types, with booleans taking part in arithmetic as ints
+fn infer_type(signature: &ParameterSignature, frame: NodeFrame<Option<ParameterType>>) -> Result<Option<ParameterType>, String> {
+    let int_like = |t: ParameterType| matches!(t, ParameterType::Int | ParameterType::Bool);
+    let numeric = |t: ParameterType| matches!(t, ParameterType::Int | ParameterType::Bool | ParameterType::Double);
+    Ok(match frame {
+        NodeFrame::FunctionParameter(parameter) => signature.get(&parameter.identifier).map(|declaration| declaration.parameter_type),
+        NodeFrame::NumericConstant(Numeric::Int(_)) => Some(ParameterType::Int),
+        NodeFrame::NumericConstant(Numeric::Double(_)) => Some(ParameterType::Double),
+        NodeFrame::StringConstant(_) => Some(ParameterType::String),
+        NodeFrame::BooleanConstant(_) => Some(ParameterType::Bool),
+        NodeFrame::BitVec(bv) => Some(ParameterType::BitVec(bv.length)),
+        NodeFrame::BinOp(binop) => {
+            let (Some(left), Some(right)) = (binop.left, binop.right) else {
+                return Ok(None);
+            };
+            let operation = binop.operation;
+            let bitwise_combination = matches!(operation, BinaryOperation::BitwiseAnd | BinaryOperation::BitwiseOr | BinaryOperation::BitwiseXor);
+            Some(match (left, right) {
+                (ParameterType::Bool, ParameterType::Bool) if bitwise_combination => ParameterType::Bool,
+                (l, r) if int_like(l) && int_like(r) => ParameterType::Int,
+                (l, r) if numeric(l) && numeric(r) && !operation.is_bitwise() => ParameterType::Double,
+                (ParameterType::String, ParameterType::String) if operation == BinaryOperation::Add => ParameterType::String,
+                (ParameterType::String, count) | (count, ParameterType::String) if operation == BinaryOperation::Multiply && int_like(count) => ParameterType::String,
+                (ParameterType::BitVec(l), ParameterType::BitVec(r)) if bitwise_combination && l == r => ParameterType::BitVec(l),
+                (l, r) => return Err(format!("{operation:?} is not defined between {l:?} and {r:?}")),
+            })
+        }
+        NodeFrame::UnaryOp(unaryop) => match (unaryop.operation, unaryop.operand) {
+            (UnaryOperation::Not, _) => Some(ParameterType::Bool),
+            (_, None) => None,
+            (UnaryOperation::UnaryMinus, Some(ParameterType::Double)) => Some(ParameterType::Double),
+            (UnaryOperation::UnaryMinus | UnaryOperation::Invert, Some(operand)) if int_like(operand) => Some(ParameterType::Int),
+            (UnaryOperation::Invert, Some(ParameterType::BitVec(length))) => Some(ParameterType::BitVec(length)),
+            (operation, Some(operand)) => return Err(format!("{operation:?} is not defined for {operand:?}")),
+        },
+        NodeFrame::BoolOp(boolop) => {
+            // `and` and `or` return one of their operands
+            let first = boolop.operands.first().copied().flatten();
+            first.filter(|_| boolop.operands.iter().all(|operand| *operand == first))
+        }
+        NodeFrame::Compare(compare) => {
+            let operands: Vec<Option<ParameterType>> = std::iter::once(compare.left).chain(compare.comparators).collect();
+            for (operation, pair) in compare.operations.iter().zip(operands.windows(2)) {
+                let (Some(left), Some(right)) = (pair[0], pair[1]) else {
+                    continue;
+                };
+                let valid = match operation {
+                    ComparisonOperation::Equal | ComparisonOperation::NotEqual | ComparisonOperation::Is | ComparisonOperation::IsNot => true,
+                    ComparisonOperation::In | ComparisonOperation::NotIn => left == ParameterType::String && right == ParameterType::String,
+                    _ => (numeric(left) && numeric(right)) || (left == ParameterType::String && right == ParameterType::String),
+                };
+                if !valid {
+                    return Err(format!("{operation:?} is not defined between {left:?} and {right:?}"));
+                }
+            }
+            Some(ParameterType::Bool)
+        }
+        NodeFrame::If(if_node) => if_node.success.filter(|_| if_node.success == if_node.failure),
+        NodeFrame::Measure(measure) => measure.operand,
+        NodeFrame::Encode(encode) => encode.operand,
+    })
+}
+
+#[cfg(test)]
+mod signature_tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn signature() -> ParameterSignature {
+        ParameterSignature::new()
+            .with_parameter("i", ComputingDomain::Classical, ParameterType::Int)
+            .with_parameter("q", ComputingDomain::Quantum, ParameterType::BitVec(4))
+    }
+
+    #[test]
+    fn declared_domains_drive_parameters() -> anyhow::Result<()> {
+        let signature = signature();
+        assert_eq!(ComputingDomain::Classical, signature.domain_of(&parse("i + 1 < 10")?)?);
+        assert_eq!(ComputingDomain::Quantum, signature.domain_of(&parse("q ^ q")?)?);
+        assert_eq!(ComputingDomain::Conflict, signature.domain_of(&parse("q << i")?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn undeclared_parameters_are_reported() -> anyhow::Result<()> {
+        let error = signature().domain_of(&parse("i + (q & r)")?).expect_err("r is not declared");
+        assert_eq!(UndeclaredParameter { name: "r".to_string(), path: NodePath::from_indices(vec![1, 1]) }, error);
+        Ok(())
+    }
+
+    #[test]
+    fn types_are_inferred_from_declarations() -> anyhow::Result<()> {
+        let signature = signature()
+            .with_parameter("s", ComputingDomain::Classical, ParameterType::String)
+            .with_parameter("d", ComputingDomain::Classical, ParameterType::Double);
+        assert_eq!(Some(ParameterType::Int), signature.type_of(&parse("i * 2 + True")?)?);
+        assert_eq!(Some(ParameterType::Double), signature.type_of(&parse("i / d")?)?);
+        assert_eq!(Some(ParameterType::String), signature.type_of(&parse("s * i + 'x'")?)?);
+        assert_eq!(Some(ParameterType::BitVec(4)), signature.type_of(&parse("~(q ^ q)")?)?);
+        assert_eq!(Some(ParameterType::Bool), signature.type_of(&parse("not s or i < d")?)?);
+        assert_eq!(None, signature.type_of(&parse("i + x")?)?);
+        assert_eq!(None, signature.type_of(&parse("i if d else s")?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_operations_on_declared_types_are_reported() -> anyhow::Result<()> {
+        let signature = signature().with_parameter("s", ComputingDomain::Classical, ParameterType::String);
+        let error = signature.type_of(&parse("i + (s - 1)")?).expect_err("strings cannot be subtracted");
+        assert_eq!(NodePath::from_indices(vec![1]), error.path);
+        assert!(signature.type_of(&parse("s < i")?).is_err());
+        assert!(signature.type_of(&parse("q & 0b01")?).is_err());
+        assert!(signature.type_of(&parse("-s")?).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn redeclaring_replaces_the_declaration() {
+        let mut signature = signature();
+        signature.declare("i", ComputingDomain::Quantum, ParameterType::Int);
+        assert_eq!(2, signature.parameters().len());
+        assert_eq!(Some(ComputingDomain::Quantum), signature.get("i").map(|declaration| declaration.domain));
+    }
+}
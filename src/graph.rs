@@ -1,4 +1,15 @@
+pub mod alpha_equivalence;
+pub mod arena;
 pub mod boxed_nodes;
+pub mod canonicalization;
+pub mod common_subexpressions;
+pub mod dot;
+pub(crate) mod fresh_names;
 pub mod node_frame;
+pub mod node_path;
 pub mod node_transformer;
-mod structure_key;
+mod printer;
+#[cfg(feature = "serde")]
+pub mod serialization;
+pub mod structure_key;
+pub mod transform_error;
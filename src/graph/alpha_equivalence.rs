@@ -0,0 +1,102 @@
+use std::collections::{BTreeMap, HashMap};
+use recursion::{CollapsibleExt, Expandable};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::NodeFrame;
+use crate::graph::node_path::{collapse_with_paths, PathFrame};
+use crate::graph::structure_key::{StructuralIdentifier, StructureKey};
+
+// A structure key that ignores parameter names.
+// Parameters are numbered by their first use in path order, which visits the children of a node in order,
+// so `x & 1` and `y & 1` share a key while `x + y` and `x + x` do not
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PositionalKey {
+    pub key: StructureKey,
+    // The name of every ParameterIndex in the key
+    pub parameters: Vec<String>,
+}
+
+pub fn positional_structure_key(node: &BoxedNode) -> PositionalKey {
+    let mut uses = Vec::new();
+    collapse_with_paths(node, |PathFrame { path, frame }: PathFrame<()>| {
+        if let NodeFrame::FunctionParameter(parameter) = frame {
+            uses.push((path, parameter.identifier));
+        }
+    });
+    // Sorting paths lists a parent before its children and earlier children before later ones
+    uses.sort();
+
+    let mut parameters: Vec<String> = Vec::new();
+    let mut indices = HashMap::new();
+    for (_, name) in uses {
+        if !indices.contains_key(&name) {
+            indices.insert(name.clone(), parameters.len());
+            parameters.push(name);
+        }
+    }
+
+    let key = node.collapse_frames(|frame: NodeFrame<StructureKey>| match frame {
+        NodeFrame::FunctionParameter(parameter) => StructureKey {
+            contents: vec![StructuralIdentifier::ParameterIndex(indices[&parameter.identifier])],
+        },
+        other => StructureKey::from_frame(other),
+    });
+    PositionalKey {
+        key,
+        parameters,
+    }
+}
+
+// Returns how the parameters of `from` are renamed into the ones of `to`,
+// or None when no renaming makes the graphs structurally identical
+pub fn alpha_equivalence(from: &BoxedNode, to: &BoxedNode) -> Option<BTreeMap<String, String>> {
+    let (from, to) = (positional_structure_key(from), positional_structure_key(to));
+    if from.key != to.key {
+        return None;
+    }
+    Some(from.parameters.into_iter().zip(to.parameters).collect())
+}
+
+#[cfg(test)]
+mod alpha_equivalence_tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn renaming(from: &str, to: &str) -> anyhow::Result<Option<Vec<(String, String)>>> {
+        let renaming = alpha_equivalence(&parse(from)?, &parse(to)?);
+        Ok(renaming.map(|renaming| renaming.into_iter().collect()))
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(pairs.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect())
+    }
+
+    #[test]
+    fn renamed_parameters_are_equivalent() -> anyhow::Result<()> {
+        assert_eq!(pairs(&[("x", "y")]), renaming("x & 1", "y & 1")?);
+        assert_eq!(pairs(&[("x", "y"), ("y", "x")]), renaming("x + y * x", "y + x * y")?);
+        assert_eq!(pairs(&[]), renaming("1 + 2", "1 + 2")?);
+        Ok(())
+    }
+
+    #[test]
+    fn sharing_must_match() -> anyhow::Result<()> {
+        assert_eq!(None, renaming("x + x", "x + y")?);
+        assert_eq!(None, renaming("x + y", "a + a")?);
+        Ok(())
+    }
+
+    #[test]
+    fn structure_must_match() -> anyhow::Result<()> {
+        assert_eq!(None, renaming("x & 1", "x & 2")?);
+        assert_eq!(None, renaming("x & 1", "1 & x")?);
+        Ok(())
+    }
+
+    #[test]
+    fn parameters_are_numbered_in_path_order() -> anyhow::Result<()> {
+        // The condition is the first child of an If
+        let key = positional_structure_key(&parse("b if c else a + b")?);
+        assert_eq!(vec!["c", "b", "a"], key.parameters);
+        Ok(())
+    }
+}
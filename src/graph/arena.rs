@@ -0,0 +1,259 @@
+// A DAG alternative to BoxedNode: every node lives once in an arena and refers to its children by NodeId.
+// Nodes with the same StructuralIdentifier and children are interned, so a repeated subexpression is stored once.
+// transform_frames rewrites the DAG in place of the tree, NodeTransformers still see their children as trees
+use std::collections::HashMap;
+use recursion::{Collapsible, CollapsibleExt, ExpandableExt, MappableFrame, PartiallyApplied};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::NodeFrame;
+use crate::graph::node_transformer::NodeTransformer;
+use crate::graph::structure_key::StructuralIdentifier;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+
+    pub(crate) fn from_index(index: usize) -> Self {
+        Self(index)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    // Children always have a smaller id than their parents
+    nodes: Vec<NodeFrame<NodeId>>,
+    interned: HashMap<(StructuralIdentifier, Vec<NodeId>), NodeId>,
+}
+
+impl Graph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_tree(node: &BoxedNode) -> (Self, NodeId) {
+        let mut graph = Self::new();
+        let root = graph.insert_tree(node);
+        (graph, root)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Returns the existing id when an identical node was added before.
+    // Panics when a child does not belong to this graph
+    pub fn add(&mut self, frame: NodeFrame<NodeId>) -> NodeId {
+        let children: Vec<NodeId> = frame.children().into_iter().copied().collect();
+        if let Some(child) = children.iter().find(|child| child.0 >= self.nodes.len()) {
+            panic!("node {} does not belong to this graph", child.0);
+        }
+        let key = (StructuralIdentifier::of_node(&frame), children);
+        if let Some(id) = self.interned.get(&key) {
+            return *id;
+        }
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(frame);
+        self.interned.insert(key, id);
+        id
+    }
+
+    pub fn get(&self, id: NodeId) -> &NodeFrame<NodeId> {
+        &self.nodes[id.0]
+    }
+
+    pub fn node(&self, id: NodeId) -> GraphNode<'_> {
+        GraphNode { graph: self, id }
+    }
+
+    pub fn insert_tree(&mut self, node: &BoxedNode) -> NodeId {
+        node.collapse_frames(|frame: NodeFrame<NodeId>| self.add(frame))
+    }
+
+    // Shared nodes are copied once for every parent using them
+    pub fn to_tree(&self, id: NodeId) -> BoxedNode {
+        BoxedNode::expand_frames(id, |id| self.get(id).clone())
+    }
+
+    // Runs the transformer over the nodes reachable from root, calling it once for each shared node,
+    // and adds the results to this graph.
+    // NodeTransformers take their children as BoxedNodes, so every child is expanded into a tree first:
+    // the work grows with the size of the expanded tree, which can be exponential in the size of the DAG.
+    // Use transform_frames to stay proportional to the DAG.
+    // The transform_* methods are called through transform_frame, an overridden transform_node is not used
+    pub fn transform(&mut self, root: NodeId, transformer: &mut impl NodeTransformer) -> Option<NodeId> {
+        self.transform_frames(root, |graph, frame| {
+            let frame = frame.map(|child| child.map(|transformed| graph.to_tree(transformed)));
+            let transformed = transformer.transform_frame(frame)?;
+            Some(graph.insert_tree(&transformed))
+        })
+    }
+
+    // Rewrites the nodes reachable from root bottom up, once per node, without expanding shared nodes.
+    // The rewrite gets each node with its children replaced by their rewritten ids, None for dropped children,
+    // and returns the id of the replacement, adding new nodes to the graph it is handed
+    pub fn transform_frames(&mut self, root: NodeId, mut rewrite: impl FnMut(&mut Graph, NodeFrame<Option<NodeId>>) -> Option<NodeId>) -> Option<NodeId> {
+        let reachable = self.reachable(root);
+        let mut results: Vec<Option<Option<NodeId>>> = vec![None; root.0 + 1];
+        for index in (0..=root.0).filter(|index| reachable[*index]) {
+            let frame = self.nodes[index].clone().map(|child| {
+                results[child.0].expect("children are transformed before their parents")
+            });
+            results[index] = Some(rewrite(self, frame));
+        }
+        results[root.0].flatten()
+    }
+
+    fn reachable(&self, root: NodeId) -> Vec<bool> {
+        let mut reachable = vec![false; root.0 + 1];
+        let mut pending = vec![root];
+        while let Some(id) = pending.pop() {
+            if !reachable[id.0] {
+                reachable[id.0] = true;
+                pending.extend(self.get(id).children().into_iter().copied());
+            }
+        }
+        reachable
+    }
+}
+
+// A node together with the graph it lives in, which lets the recursion traversals walk a Graph
+// the same way they walk a BoxedNode. Shared nodes are visited once per parent
+#[derive(Debug, Clone, Copy)]
+pub struct GraphNode<'a> {
+    graph: &'a Graph,
+    id: NodeId,
+}
+
+impl<'a> GraphNode<'a> {
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn frame(&self) -> &'a NodeFrame<NodeId> {
+        self.graph.get(self.id)
+    }
+}
+
+impl<'a> Collapsible for GraphNode<'a> {
+    type FrameToken = NodeFrame<PartiallyApplied>;
+
+    fn into_frame(self) -> <Self::FrameToken as MappableFrame>::Frame<Self> {
+        let graph = self.graph;
+        self.frame().clone().map(|id| GraphNode { graph, id })
+    }
+}
+
+#[cfg(test)]
+mod arena_tests {
+    use super::*;
+    use crate::graph::node_frame::{BinOp, FunctionParameter, Numeric, UnaryOp};
+    use crate::operations::{BinaryOperation, UnaryOperation};
+    use crate::parser::parse;
+
+    #[test]
+    fn identical_subtrees_are_stored_once() -> anyhow::Result<()> {
+        let (graph, root) = Graph::from_tree(&parse("(x ^ y) & (x ^ y) | x")?);
+        // x, y, x ^ y, &, |
+        assert_eq!(5, graph.len());
+        assert!(matches!(graph.get(root), NodeFrame::BinOp(BinOp { operation: BinaryOperation::BitwiseOr, .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn doubles_are_interned_like_structure_keys() -> anyhow::Result<()> {
+        let (graph, _) = Graph::from_tree(&parse("0.0 + -0.0 + 0.0")?);
+        // 0.0, -0.0 and the two additions
+        assert_eq!(4, graph.len());
+        Ok(())
+    }
+
+    #[test]
+    fn trees_round_trip() -> anyhow::Result<()> {
+        let tree = parse("measure(q & 0b01) + 1.5 if 'a' in s < 3 else -(x ^ x)")?;
+        let (graph, root) = Graph::from_tree(&tree);
+        assert_eq!(tree.get_structure_key(), graph.to_tree(root).get_structure_key());
+        Ok(())
+    }
+
+    #[test]
+    fn traversals_walk_shared_nodes_once_per_parent() -> anyhow::Result<()> {
+        let (graph, root) = Graph::from_tree(&parse("(a + b) * (a + b)")?);
+        let tree_size = graph.node(root).collapse_frames(|frame: NodeFrame<usize>| {
+            1 + frame.children().into_iter().sum::<usize>()
+        });
+        assert_eq!(7, tree_size);
+        Ok(())
+    }
+
+    struct NegateParameters {
+        visited: usize,
+    }
+
+    impl NodeTransformer for NegateParameters {
+        fn transform_function_parameter(&mut self, parameter: FunctionParameter) -> Option<BoxedNode> {
+            self.visited += 1;
+            Some(BoxedNode::unary(UnaryOperation::UnaryMinus, BoxedNode::parameter(parameter.identifier)))
+        }
+    }
+
+    #[test]
+    fn transformers_visit_shared_nodes_once() -> anyhow::Result<()> {
+        let (mut graph, root) = Graph::from_tree(&parse("x * x + x")?);
+        let mut transformer = NegateParameters { visited: 0 };
+        let transformed = graph.transform(root, &mut transformer).expect("nothing is dropped");
+
+        assert_eq!(1, transformer.visited);
+        assert_eq!("-x * -x + -x", graph.to_tree(transformed).to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn frame_rewrites_stay_proportional_to_the_dag() -> anyhow::Result<()> {
+        // x + x + ... nested 64 times expands into a tree with 2^64 leaves
+        let mut graph = Graph::new();
+        let mut root = graph.add(NodeFrame::FunctionParameter(FunctionParameter { identifier: "x".to_string() }));
+        for _ in 0..64 {
+            root = graph.add(NodeFrame::BinOp(BinOp { operation: BinaryOperation::Add, left: root, right: root }));
+        }
+
+        let mut visited = 0;
+        let one = graph.add(NodeFrame::NumericConstant(Numeric::Int(1)));
+        let doubled = graph.transform_frames(root, |graph, frame| {
+            visited += 1;
+            let frame = frame.try_map(|child| child.ok_or(())).ok()?;
+            // x + x is x << 1, interning makes equal children equal ids
+            let frame = match frame {
+                NodeFrame::BinOp(BinOp { operation: BinaryOperation::Add, left, right }) if left == right => {
+                    NodeFrame::BinOp(BinOp { operation: BinaryOperation::BitwiseLeftShift, left, right: one })
+                }
+                frame => frame,
+            };
+            Some(graph.add(frame))
+        }).expect("nothing is dropped");
+
+        assert_eq!(65, visited);
+        let mut shifts = 0;
+        let mut id = doubled;
+        while let NodeFrame::BinOp(BinOp { operation: BinaryOperation::BitwiseLeftShift, left, .. }) = graph.get(id) {
+            shifts += 1;
+            id = *left;
+        }
+        assert_eq!(64, shifts);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "does not belong to this graph")]
+    fn foreign_ids_are_rejected() {
+        let mut other = Graph::new();
+        let parameter = other.add(NodeFrame::FunctionParameter(FunctionParameter { identifier: "x".to_string() }));
+        Graph::new().add(NodeFrame::UnaryOp(UnaryOp { operation: UnaryOperation::Invert, operand: parameter }));
+    }
+}
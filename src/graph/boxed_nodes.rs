@@ -1,26 +1,105 @@
-use std::ops::Deref;
+use std::collections::BTreeSet;
 use recursion::{Collapsible, CollapsibleExt, Expandable, MappableFrame, PartiallyApplied};
 use crate::computing::{Computable, ComputingDomain};
-use crate::graph::node_frame::{BinOp, UnaryOp, NodeFrame, BoolOp, Compare, If};
+use crate::computing::signature::ParameterSignature;
+use crate::graph::node_frame::{BinOp, UnaryOp, NodeFrame, BoolOp, Compare, If, Measure, Encode, FunctionParameter, Numeric};
 use crate::graph::structure_key::StructureKey;
+use crate::operations::{BinaryOperation, BooleanOperation, ComparisonOperation, UnaryOperation};
 
 #[derive(Debug, Clone)]
 pub struct BoxedNode {
     pub data: NodeFrame<Box<BoxedNode>>,
 }
 
+// Constructors for building graphs by hand, in rewrites and in tests
+impl BoxedNode {
+    pub fn parameter(identifier: impl Into<String>) -> Self {
+        Self { data: NodeFrame::FunctionParameter(FunctionParameter { identifier: identifier.into() }) }
+    }
+
+    pub fn int(value: i32) -> Self {
+        Self { data: NodeFrame::NumericConstant(Numeric::Int(value)) }
+    }
+
+    pub fn double(value: f64) -> Self {
+        Self { data: NodeFrame::NumericConstant(Numeric::Double(value)) }
+    }
+
+    pub fn string(value: impl Into<String>) -> Self {
+        Self { data: NodeFrame::StringConstant(value.into()) }
+    }
+
+    pub fn boolean(value: bool) -> Self {
+        Self { data: NodeFrame::BooleanConstant(value) }
+    }
+
+    pub fn binary(operation: BinaryOperation, left: BoxedNode, right: BoxedNode) -> Self {
+        Self { data: NodeFrame::BinOp(BinOp { operation, left: Box::new(left), right: Box::new(right) }) }
+    }
+
+    pub fn unary(operation: UnaryOperation, operand: BoxedNode) -> Self {
+        Self { data: NodeFrame::UnaryOp(UnaryOp { operation, operand: Box::new(operand) }) }
+    }
+
+    pub fn bool_op(operator: BooleanOperation, operands: Vec<BoxedNode>) -> Self {
+        Self { data: NodeFrame::BoolOp(BoolOp { operator, operands: operands.into_iter().map(Box::new).collect() }) }
+    }
+
+    // `left op₀ c₀ op₁ c₁ ...` from the (opᵢ, cᵢ) pairs
+    pub fn compare(left: BoxedNode, chain: Vec<(ComparisonOperation, BoxedNode)>) -> Self {
+        let (operations, comparators) = chain.into_iter().map(|(operation, comparator)| (operation, Box::new(comparator))).unzip();
+        Self { data: NodeFrame::Compare(Compare { left: Box::new(left), operations, comparators }) }
+    }
+
+    pub fn if_else(condition: BoxedNode, success: BoxedNode, failure: BoxedNode) -> Self {
+        Self { data: NodeFrame::If(If { condition: Box::new(condition), success: Box::new(success), failure: Box::new(failure) }) }
+    }
+}
+
 impl BoxedNode {
     pub fn get_structure_key(self) -> StructureKey {
-        let x = self.collapse_frames(|x| {
+        self.collapse_frames(|x| {
            StructureKey::from_frame(x)
+        })
+    }
+
+    pub fn parameter_names(&self) -> BTreeSet<String> {
+        let mut names = BTreeSet::new();
+        self.collapse_frames(|frame: NodeFrame<()>| {
+            if let NodeFrame::FunctionParameter(parameter) = frame {
+                names.insert(parameter.identifier);
+            }
         });
-        x
+        names
+    }
+
+    // Whether evaluating the node can neither fail nor measure, so a rewrite may skip evaluating it.
+    // Arithmetic can always fail, on overflow or on operand types, while `not` and ==, !=, is and is not never do
+    pub fn is_droppable(&self) -> bool {
+        self.collapse_frames(|frame: NodeFrame<bool>| match frame {
+            NodeFrame::FunctionParameter(_)
+            | NodeFrame::NumericConstant(_)
+            | NodeFrame::StringConstant(_)
+            | NodeFrame::BooleanConstant(_)
+            | NodeFrame::BitVec(_) => true,
+            NodeFrame::UnaryOp(unaryop) => unaryop.operation == UnaryOperation::Not && unaryop.operand,
+            NodeFrame::BoolOp(boolop) => boolop.operands.into_iter().all(|operand| operand),
+            NodeFrame::Compare(compare) => {
+                let total = compare.operations.iter().all(|operation| {
+                    matches!(operation, ComparisonOperation::Equal | ComparisonOperation::NotEqual | ComparisonOperation::Is | ComparisonOperation::IsNot)
+                });
+                total && compare.left && compare.comparators.into_iter().all(|comparator| comparator)
+            }
+            NodeFrame::If(if_node) => if_node.condition && if_node.success && if_node.failure,
+            NodeFrame::Encode(encode) => encode.operand,
+            NodeFrame::BinOp(_) | NodeFrame::Measure(_) => false,
+        })
     }
 }
 
 impl Computable for BoxedNode {
-    fn get_domain(&self) -> ComputingDomain {
-        self.data.get_domain()
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        self.data.get_domain(signature)
     }
 }
 
@@ -73,6 +152,8 @@ impl Expandable for BoxedNode {
                 };
                 NodeFrame::If(new_if)
             }
+            NodeFrame::Measure(measure) => NodeFrame::Measure(Measure { operand: Box::new(measure.operand) }),
+            NodeFrame::Encode(encode) => NodeFrame::Encode(Encode { operand: Box::new(encode.operand) }),
         };
         Self {
             data: boxed,
@@ -128,6 +209,17 @@ impl Collapsible for BoxedNode {
                 };
                 NodeFrame::If(new_if)
             }
+            NodeFrame::Measure(measure) => NodeFrame::Measure(Measure { operand: *measure.operand }),
+            NodeFrame::Encode(encode) => NodeFrame::Encode(Encode { operand: *encode.operand }),
         }
     }
-}
\ No newline at end of file
+}
+
+// Traversing by reference avoids cloning the whole tree when a pass only needs to read it
+impl Collapsible for &BoxedNode {
+    type FrameToken = NodeFrame<PartiallyApplied>;
+
+    fn into_frame(self) -> <Self::FrameToken as MappableFrame>::Frame<Self> {
+        self.data.as_ref().map(|child| child.as_ref())
+    }
+}
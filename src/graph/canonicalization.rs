@@ -0,0 +1,263 @@
+use recursion::{CollapsibleExt, Expandable};
+use crate::computing::signature::{ParameterSignature, ParameterType};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{BinOp, BoolOp, Compare, NodeFrame};
+use crate::graph::node_transformer::NodeTransformer;
+use crate::graph::structure_key::StructureKey;
+use crate::operations::{BinaryOperation, ComparisonOperation};
+
+// Rewrites graphs into a canonical form, so graphs that only differ in the order of their operands
+// end up with the same StructureKey:
+// - chains of a commutative BinOp are flattened, sorted by StructureKey and rebuilt left associated
+// - nested BoolOps with the same operator are flattened, keeping their operands in order
+// - a single comparison only uses < and <=, and the operands of a single ==, !=, is or is not are sorted
+//
+// The rewritten graph evaluates like the original one. Operands of a BinOp are only sorted when the signature
+// says they are all numbers, `s * 2 + y` may be a string concatenation, which does not commute.
+// BoolOps short-circuit and chained comparisons stop at the first false link, so their order is kept;
+// canonical_key additionally sorts BoolOp operands for callers that only compare structure
+#[derive(Debug, Clone)]
+pub struct Canonicalizer {
+    // Reassociating + and * changes how doubles are rounded, when set only int chains are reassociated
+    pub exact_floating_point: bool,
+    // Types of the parameters, operands whose type cannot be inferred keep their order
+    pub signature: ParameterSignature,
+}
+
+impl Default for Canonicalizer {
+    fn default() -> Self {
+        Self {
+            exact_floating_point: true,
+            signature: ParameterSignature::default(),
+        }
+    }
+}
+
+impl Canonicalizer {
+    pub fn with_signature(signature: ParameterSignature) -> Self {
+        Self {
+            signature,
+            ..Self::default()
+        }
+    }
+
+    // StructureKey of the canonical graph, with the operands of every BoolOp sorted.
+    // Equal keys mean equal graphs up to operand order, not that both evaluate the same way
+    pub fn canonical_key(&mut self, node: BoxedNode) -> StructureKey {
+        let node = self.transform_node(node).expect("canonicalization never drops a node");
+        node.collapse_frames(|frame: NodeFrame<StructureKey>| match frame {
+            NodeFrame::BoolOp(mut boolop) => {
+                boolop.operands.sort();
+                StructureKey::from_frame(NodeFrame::BoolOp(boolop))
+            }
+            other => StructureKey::from_frame(other),
+        })
+    }
+
+    fn known_type(&self, node: &BoxedNode) -> Option<ParameterType> {
+        self.signature.type_of(node).ok().flatten()
+    }
+}
+
+pub fn canonicalize(node: BoxedNode) -> BoxedNode {
+    Canonicalizer::default()
+        .transform_node(node)
+        .expect("canonicalization never drops a node")
+}
+
+pub fn canonical_key(node: BoxedNode) -> StructureKey {
+    Canonicalizer::default().canonical_key(node)
+}
+
+impl NodeTransformer for Canonicalizer {
+    fn transform_binary_operation(&mut self, parameter: BinOp<Option<BoxedNode>>) -> Option<BoxedNode> {
+        let operation = parameter.operation;
+        let (left, right) = (parameter.left?, parameter.right?);
+        if !operation.is_commutative() {
+            return Some(BoxedNode::binary(operation, left, right));
+        }
+
+        let int_like = |t: Option<ParameterType>| matches!(t, Some(ParameterType::Int | ParameterType::Bool));
+        let numeric = |t: Option<ParameterType>| matches!(t, Some(ParameterType::Int | ParameterType::Bool | ParameterType::Double));
+        let bitvec = |t: Option<ParameterType>| matches!(t, Some(ParameterType::BitVec(_)));
+
+        // Flattening keeps the operand order, so it only has to be exact on doubles
+        let pair = vec![left, right];
+        let flattened = flatten_chain(operation, pair.clone());
+        let reassociate = operation.is_bitwise()
+            || !self.exact_floating_point
+            || flattened.iter().all(|operand| int_like(self.known_type(operand)));
+        let mut operands = if reassociate { flattened } else { pair };
+
+        let types: Vec<_> = operands.iter().map(|operand| self.known_type(operand)).collect();
+        let commutes = types.iter().all(|t| numeric(*t)) || (operation.is_bitwise() && types.iter().all(|t| bitvec(*t)));
+        if commutes {
+            sort_by_structure(&mut operands);
+        }
+
+        let mut operands = operands.into_iter();
+        let first = operands.next().expect("a BinOp has two operands");
+        Some(operands.fold(first, |chain, operand| BoxedNode::binary(operation, chain, operand)))
+    }
+
+    fn transform_boolean_operation(&mut self, parameter: BoolOp<Vec<Option<BoxedNode>>>) -> Option<BoxedNode> {
+        let mut operands = Vec::new();
+        for operand in parameter.operands.into_iter().flatten() {
+            // Operands are already canonical, so a nested BoolOp is flat itself
+            match operand.data {
+                NodeFrame::BoolOp(nested) if nested.operator == parameter.operator => {
+                    operands.extend(nested.operands.into_iter().map(|nested| *nested));
+                }
+                data => operands.push(BoxedNode { data }),
+            }
+        }
+        Some(BoxedNode::bool_op(parameter.operator, operands))
+    }
+
+    fn transform_comparison(&mut self, parameter: Compare<Option<BoxedNode>, Vec<Option<BoxedNode>>>) -> Option<BoxedNode> {
+        let mut left = parameter.left?;
+        let mut operations = parameter.operations;
+        let mut comparators: Vec<BoxedNode> = parameter.comparators.into_iter().collect::<Option<_>>()?;
+
+        // Both operands of a single comparison are always evaluated, so they can trade places
+        if let [operation] = operations.as_slice()
+            && let [right] = comparators.as_mut_slice()
+        {
+            let mirrored = operation.mirrored();
+            let descending = matches!(operation, ComparisonOperation::GreaterThan | ComparisonOperation::GreaterThanOrEqual);
+            let symmetric = mirrored == Some(*operation);
+            if descending || (symmetric && right.clone().get_structure_key() < left.clone().get_structure_key()) {
+                std::mem::swap(&mut left, right);
+                operations = vec![mirrored.expect("descending and symmetric comparisons can be mirrored")];
+            }
+        }
+
+        Some(BoxedNode {
+            data: NodeFrame::Compare(Compare {
+                left: Box::new(left),
+                operations,
+                comparators: comparators.into_iter().map(Box::new).collect(),
+            })
+        })
+    }
+}
+
+// The operands of a chain of the same operation, in order, without recursing on long chains
+fn flatten_chain(operation: BinaryOperation, roots: Vec<BoxedNode>) -> Vec<BoxedNode> {
+    let mut operands = Vec::new();
+    let mut pending: Vec<BoxedNode> = roots.into_iter().rev().collect();
+    while let Some(node) = pending.pop() {
+        match node.data {
+            NodeFrame::BinOp(binop) if binop.operation == operation => {
+                pending.push(*binop.right);
+                pending.push(*binop.left);
+            }
+            data => operands.push(BoxedNode { data }),
+        }
+    }
+    operands
+}
+
+fn sort_by_structure(operands: &mut Vec<BoxedNode>) {
+    let mut keyed: Vec<_> = operands.drain(..).map(|operand| (operand.clone().get_structure_key(), operand)).collect();
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    operands.extend(keyed.into_iter().map(|(_, operand)| operand));
+}
+
+#[cfg(test)]
+mod canonicalization_tests {
+    use super::*;
+    use crate::computing::ComputingDomain;
+    use crate::parser::parse;
+
+    // a, b and c are ints, x, y and z doubles, s a string
+    fn typed() -> Canonicalizer {
+        let signature = [("a", ParameterType::Int), ("b", ParameterType::Int), ("c", ParameterType::Int),
+            ("x", ParameterType::Double), ("y", ParameterType::Double), ("z", ParameterType::Double), ("s", ParameterType::String)]
+            .into_iter()
+            .fold(ParameterSignature::new(), |signature, (name, parameter_type)| {
+                signature.with_parameter(name, ComputingDomain::Classical, parameter_type)
+            });
+        Canonicalizer::with_signature(signature)
+    }
+
+    fn canonical_with(canonicalizer: &mut Canonicalizer, source: &str) -> anyhow::Result<String> {
+        Ok(canonicalizer.transform_node(parse(source)?).expect("nothing is dropped").to_string())
+    }
+
+    fn canonical(source: &str) -> anyhow::Result<String> {
+        canonical_with(&mut typed(), source)
+    }
+
+    fn assert_equivalent(a: &str, b: &str) -> anyhow::Result<()> {
+        let (a_key, b_key) = (typed().canonical_key(parse(a)?), typed().canonical_key(parse(b)?));
+        assert_eq!(a_key, b_key, "{a:?} and {b:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn commutative_operands_are_sorted() -> anyhow::Result<()> {
+        assert_equivalent("a + b", "b + a")?;
+        assert_equivalent("x * (a & b)", "(b & a) * x")?;
+        assert_eq!("a + 1", canonical("1 + a")?);
+        assert_eq!("a - b", canonical("a - b")?);
+        assert_ne!(canonical("a - b")?, canonical("b - a")?);
+        Ok(())
+    }
+
+    #[test]
+    fn int_chains_are_flattened() -> anyhow::Result<()> {
+        assert_equivalent("(c + a) + b", "a + (b + c)")?;
+        assert_eq!("a ^ b ^ c ^ 1", canonical("1 ^ (b ^ (c ^ a))")?);
+        // Different operations do not mix
+        assert_eq!("c * (a + b)", canonical("(b + a) * c")?);
+        Ok(())
+    }
+
+    #[test]
+    fn floating_point_is_exact_by_default() -> anyhow::Result<()> {
+        assert_eq!("x + (y + z)", canonical("(z + y) + x")?);
+        assert_eq!("x + (a + 1)", canonical("a + 1 + x")?);
+
+        let mut inexact = Canonicalizer { exact_floating_point: false, ..typed() };
+        assert_eq!("x + y + z", canonical_with(&mut inexact, "(z + y) + x")?);
+        Ok(())
+    }
+
+    #[test]
+    fn operands_of_unknown_type_keep_their_order() -> anyhow::Result<()> {
+        assert_eq!("s * 2 + y", canonical("s * 2 + y")?);
+        assert_eq!("'b' + (x + 'a')", canonical("'b' + (x + 'a')")?);
+        assert_eq!("q + a", canonical("q + a")?);
+        assert_eq!("q * 2", canonicalize(parse("q * 2")?).to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn boolean_operations_are_flattened_in_order() -> anyhow::Result<()> {
+        assert_eq!("a != 0 and 1 < b / a", canonical("a != 0 and b / a > 1")?);
+        assert_eq!("x and z and y", canonical("x and (z and y)")?);
+        assert_eq!("(b or a) and c", canonical("(b or a) and c")?);
+        Ok(())
+    }
+
+    #[test]
+    fn canonical_keys_ignore_boolean_operand_order() -> anyhow::Result<()> {
+        assert_equivalent("x and (z and y)", "y and x and z")?;
+        assert_equivalent("c and (a or b)", "(b or a) and c")?;
+        assert_eq!(canonical_key(parse("p and q")?), canonical_key(parse("q and p")?));
+        Ok(())
+    }
+
+    #[test]
+    fn comparisons_are_normalized() -> anyhow::Result<()> {
+        assert_equivalent("a > b", "b < a")?;
+        assert_equivalent("a == b", "b == a")?;
+        assert_equivalent("x is not 1", "1 is not x")?;
+        assert_eq!("b in a", canonical("b in a")?);
+        assert_eq!("a < b > c", canonical("a < b > c")?);
+        // A chain stops at its first false link, reversing it would evaluate c before a and b
+        assert_eq!("a >= b > c", canonical("a >= b > c")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+use recursion::ExpandableExt;
+use crate::graph::arena::{Graph, NodeId};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::fresh_names::FreshNames;
+use crate::graph::node_frame::{FunctionParameter, NodeFrame};
+
+// A subexpression computed once and referred to through its parameter
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub parameter: String,
+    pub expression: BoxedNode,
+}
+
+// `let` bindings followed by the body using them.
+// A binding only refers to parameters bound before it
+#[derive(Debug, Clone)]
+pub struct SharedForm {
+    pub bindings: Vec<Binding>,
+    pub body: BoxedNode,
+}
+
+impl SharedForm {
+    // Substitutes every binding back, giving a graph structurally identical to the original
+    pub fn inline(&self) -> BoxedNode {
+        let mut expanded: Vec<(String, BoxedNode)> = Vec::with_capacity(self.bindings.len());
+        for binding in &self.bindings {
+            let expression = substitute(&binding.expression, &expanded);
+            expanded.push((binding.parameter.clone(), expression));
+        }
+        substitute(&self.body, &expanded)
+    }
+}
+
+fn substitute(node: &BoxedNode, bindings: &[(String, BoxedNode)]) -> BoxedNode {
+    BoxedNode::expand_frames(node, |node| {
+        if let NodeFrame::FunctionParameter(parameter) = &node.data
+            && let Some((_, expression)) = bindings.iter().find(|(name, _)| *name == parameter.identifier)
+        {
+            return expression.data.as_ref().map(|child| child.as_ref());
+        }
+        node.data.as_ref().map(|child| child.as_ref())
+    })
+}
+
+// Binds every subtree that occurs more than once, so it is evaluated a single time.
+// Subtrees are the same when they are structurally identical, which the arena Graph detects while interning,
+// and only the outermost repeated subtree is bound when a repeated subtree is only used inside another one.
+// Evaluation order is preserved by only binding subtrees that are evaluated whatever the values are:
+// hoisting the branch of an If or a short circuited operand could raise an error the original never would
+pub fn eliminate_common_subexpressions(node: &BoxedNode) -> SharedForm {
+    let (graph, root) = Graph::from_tree(node);
+
+    let mut uses = vec![0usize; graph.len()];
+    let mut unconditional = vec![false; graph.len()];
+    unconditional[root.index()] = true;
+    // Parents always come after their children
+    for index in (0..graph.len()).rev() {
+        let frame = graph.get(NodeId::from_index(index));
+        for child in frame.children() {
+            uses[child.index()] += 1;
+        }
+        if unconditional[index] {
+            for child in unconditional_children(frame) {
+                unconditional[child.index()] = true;
+            }
+        }
+    }
+
+    let mut names = FreshNames::new("shared", node.parameter_names());
+    let bound: Vec<Option<String>> = (0..graph.len())
+        .map(|index| {
+            let is_leaf = graph.get(NodeId::from_index(index)).children().is_empty();
+            (uses[index] > 1 && unconditional[index] && !is_leaf).then(|| names.next()).flatten()
+        })
+        .collect();
+
+    let bindings = bound.iter().enumerate()
+        .filter_map(|(index, parameter)| parameter.as_ref().map(|parameter| Binding {
+            parameter: parameter.clone(),
+            expression: rebuild(&graph, NodeId::from_index(index), &bound),
+        }))
+        .collect();
+    SharedForm {
+        bindings,
+        body: rebuild(&graph, root, &bound),
+    }
+}
+
+// The children evaluated whenever their parent is.
+// BoolOp only always evaluates its first operand, a comparison chain its first comparison and If its condition
+fn unconditional_children(frame: &NodeFrame<NodeId>) -> Vec<NodeId> {
+    match frame {
+        NodeFrame::BoolOp(boolop) => boolop.operands.first().copied().into_iter().collect(),
+        NodeFrame::Compare(compare) => std::iter::once(compare.left).chain(compare.comparators.first().copied()).collect(),
+        NodeFrame::If(if_node) => vec![if_node.condition],
+        other => other.children().into_iter().copied().collect(),
+    }
+}
+
+enum Expansion {
+    Node(NodeId),
+    Bound(String),
+}
+
+// The tree of a node, with bound children replaced by their parameters
+fn rebuild(graph: &Graph, id: NodeId, bound: &[Option<String>]) -> BoxedNode {
+    BoxedNode::expand_frames(Expansion::Node(id), |expansion| match expansion {
+        Expansion::Node(id) => graph.get(id).clone().map(|child| match &bound[child.index()] {
+            Some(parameter) => Expansion::Bound(parameter.clone()),
+            None => Expansion::Node(child),
+        }),
+        Expansion::Bound(identifier) => NodeFrame::FunctionParameter(FunctionParameter { identifier }),
+    })
+}
+
+#[cfg(test)]
+mod common_subexpressions_tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn shared_form(source: &str) -> anyhow::Result<(Vec<String>, String)> {
+        let node = parse(source)?;
+        let form = eliminate_common_subexpressions(&node);
+        assert_eq!(node.get_structure_key(), form.inline().get_structure_key(), "inlining {source:?}");
+        let bindings = form.bindings.iter()
+            .map(|binding| format!("{} = {}", binding.parameter, binding.expression))
+            .collect();
+        Ok((bindings, form.body.to_string()))
+    }
+
+    #[test]
+    fn repeated_subtrees_are_bound_once() -> anyhow::Result<()> {
+        let (bindings, body) = shared_form("(x ^ y) & (x ^ y)")?;
+        assert_eq!(vec!["shared_0 = x ^ y"], bindings);
+        assert_eq!("shared_0 & shared_0", body);
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_outermost_repetition_is_bound() -> anyhow::Result<()> {
+        let (bindings, body) = shared_form("(x ^ y) & z | (x ^ y) & z")?;
+        assert_eq!(vec!["shared_0 = (x ^ y) & z"], bindings);
+        assert_eq!("shared_0 | shared_0", body);
+        Ok(())
+    }
+
+    #[test]
+    fn bindings_refer_to_earlier_bindings() -> anyhow::Result<()> {
+        let (bindings, body) = shared_form("(a + b) * c + (a + b) * c + (a + b)")?;
+        assert_eq!(vec!["shared_0 = a + b", "shared_1 = shared_0 * c"], bindings);
+        assert_eq!("shared_1 + shared_1 + shared_0", body);
+        Ok(())
+    }
+
+    #[test]
+    fn conditionally_evaluated_subtrees_stay_in_place() -> anyhow::Result<()> {
+        let (bindings, _) = shared_form("x / y if c else x / y")?;
+        assert!(bindings.is_empty());
+        let (bindings, _) = shared_form("a and x / y > 0 and x / y < 1")?;
+        assert!(bindings.is_empty());
+        let (bindings, _) = shared_form("0 < a < x / y < x / y")?;
+        assert!(bindings.is_empty());
+
+        // Evaluated anyway on the left, so the branch can reuse it
+        let (bindings, body) = shared_form("x / y + (x / y if c else 0)")?;
+        assert_eq!(vec!["shared_0 = x / y"], bindings);
+        assert_eq!("shared_0 + (shared_0 if c else 0)", body);
+        Ok(())
+    }
+
+    #[test]
+    fn fresh_parameters_avoid_existing_names() -> anyhow::Result<()> {
+        let (bindings, body) = shared_form("-shared_0 * -shared_0")?;
+        assert_eq!(vec!["shared_1 = -shared_0"], bindings);
+        assert_eq!("shared_1 * shared_1", body);
+        Ok(())
+    }
+}
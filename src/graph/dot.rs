@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use recursion::Expandable;
+use crate::computing::{Computable, ComputingDomain};
+use crate::computing::signature::{ParameterSignature, UndeclaredParameter};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{NodeFrame, Numeric};
+use crate::graph::node_path::{collapse_with_paths, NodePath, PathFrame};
+use crate::graph::structure_key::StructureKey;
+
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    // Draw structurally identical subtrees once, with an edge from every place they are used
+    pub merge_shared_subtrees: bool,
+}
+
+// Renders the graph in Graphviz DOT format, with parameter domains taken from the signature.
+// Every node is labelled with its operation and computing domain and filled with the domain's color,
+// edges are labelled with the index of the child they lead to
+pub fn to_dot(node: &BoxedNode, options: &DotOptions, signature: &ParameterSignature) -> Result<String, UndeclaredParameter> {
+    let mut builder = DotBuilder {
+        statements: Vec::new(),
+        node_count: 0,
+        shared: HashMap::new(),
+        merge_shared_subtrees: options.merge_shared_subtrees,
+        signature,
+    };
+    collapse_with_paths(node, |PathFrame { path, frame }: PathFrame<Result<DrawnNode, UndeclaredParameter>>| {
+        builder.add(path, frame.try_map(|child| child)?)
+    })?;
+
+    let mut dot = String::from("digraph computation_graph {\n    node [style=filled];\n");
+    for statement in builder.statements {
+        let _ = writeln!(dot, "    {statement};");
+    }
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+struct DrawnNode {
+    id: usize,
+    domain: ComputingDomain,
+    key: Option<StructureKey>,
+}
+
+struct DotBuilder<'a> {
+    statements: Vec<String>,
+    node_count: usize,
+    shared: HashMap<StructureKey, usize>,
+    merge_shared_subtrees: bool,
+    signature: &'a ParameterSignature,
+}
+
+impl DotBuilder<'_> {
+    fn add(&mut self, path: NodePath, frame: NodeFrame<DrawnNode>) -> Result<DrawnNode, UndeclaredParameter> {
+        let domain = match &frame {
+            NodeFrame::FunctionParameter(parameter) => self.signature.lookup(&parameter.identifier, &path)?,
+            _ => frame.as_ref().map(|child| child.domain).get_domain(self.signature),
+        };
+    let label = label(&frame);
+        let children: Vec<usize> = frame.children().iter().map(|child| child.id).collect();
+
+        let key = self.merge_shared_subtrees.then(|| {
+            StructureKey::from_frame(frame.map(|child| child.key.expect("keys are computed when merging")))
+        });
+        if let Some(key) = &key
+            && let Some(id) = self.shared.get(key) {
+            return Ok(DrawnNode { id: *id, domain, key: Some(key.clone()) });
+        }
+
+        let id = self.node_count;
+        self.node_count += 1;
+        self.statements.push(format!("n{id} [label=\"{}\\n{domain:?}\", fillcolor=\"{}\"]", escape(&label), color(domain)));
+        for (index, child) in children.into_iter().enumerate() {
+            self.statements.push(format!("n{id} -> n{child} [label=\"{index}\"]"));
+        }
+        if let Some(key) = &key {
+            self.shared.insert(key.clone(), id);
+        }
+        Ok(DrawnNode { id, domain, key })
+    }
+}
+
+fn label<T>(frame: &NodeFrame<T>) -> String {
+    match frame {
+        NodeFrame::FunctionParameter(p) => p.identifier.clone(),
+        NodeFrame::NumericConstant(Numeric::Int(i)) => i.to_string(),
+        NodeFrame::NumericConstant(Numeric::Double(d)) => format!("{d:?}"),
+        NodeFrame::StringConstant(s) => format!("'{s}'"),
+        NodeFrame::BooleanConstant(b) => if *b { "True" } else { "False" }.to_string(),
+        NodeFrame::BitVec(bv) => format!("0b{}", bv.bit_string),
+        NodeFrame::BinOp(binop) => binop.operation.symbol().to_string(),
+        NodeFrame::UnaryOp(unaryop) => unaryop.operation.symbol().to_string(),
+        NodeFrame::BoolOp(boolop) => boolop.operator.symbol().to_string(),
+        NodeFrame::Compare(compare) => compare.operations.iter().map(|o| o.symbol()).collect::<Vec<_>>().join(" "),
+        NodeFrame::If(_) => "if".to_string(),
+        NodeFrame::Measure(_) => "measure".to_string(),
+        NodeFrame::Encode(_) => "encode".to_string(),
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn color(domain: ComputingDomain) -> &'static str {
+    match domain {
+        ComputingDomain::Classical => "lightblue",
+        ComputingDomain::Quantum => "plum",
+        ComputingDomain::Conflict => "tomato",
+        ComputingDomain::Unknown => "lightgray",
+    }
+}
+
+#[cfg(test)]
+mod dot_tests {
+    use super::*;
+    use crate::computing::signature::ParameterType;
+    use crate::parser::parse;
+
+    fn signature() -> ParameterSignature {
+        ParameterSignature::new()
+            .with_parameter("x", ComputingDomain::Quantum, ParameterType::BitVec(2))
+            .with_parameter("y", ComputingDomain::Quantum, ParameterType::BitVec(2))
+    }
+
+    fn node_count(dot: &str) -> usize {
+        dot.lines().filter(|line| line.contains("[label=") && !line.contains("->")).count()
+    }
+
+    #[test]
+    fn nodes_are_labelled_and_colored_by_domain() -> anyhow::Result<()> {
+        let dot = to_dot(&parse("x + 1")?, &DotOptions::default(), &signature())?;
+        let expected = "digraph computation_graph {
+    node [style=filled];
+    n0 [label=\"1\\nClassical\", fillcolor=\"lightblue\"];
+    n1 [label=\"x\\nQuantum\", fillcolor=\"plum\"];
+    n2 [label=\"+\\nConflict\", fillcolor=\"tomato\"];
+    n2 -> n1 [label=\"0\"];
+    n2 -> n0 [label=\"1\"];
+}
+";
+        assert_eq!(expected, dot);
+        Ok(())
+    }
+
+    #[test]
+    fn parameter_domains_come_from_the_signature() -> anyhow::Result<()> {
+        let signature = ParameterSignature::new()
+            .with_parameter("i", ComputingDomain::Classical, ParameterType::Int);
+        let dot = to_dot(&parse("i + 1")?, &DotOptions::default(), &signature)?;
+        assert!(dot.contains("label=\"i\\nClassical\", fillcolor=\"lightblue\""));
+        assert!(dot.contains("label=\"+\\nClassical\""));
+
+        let error = to_dot(&parse("i + j")?, &DotOptions::default(), &signature).expect_err("j is not declared");
+        assert_eq!(UndeclaredParameter { name: "j".to_string(), path: NodePath::from_indices(vec![1]) }, error);
+        Ok(())
+    }
+
+    #[test]
+    fn labels_are_escaped() -> anyhow::Result<()> {
+        let dot = to_dot(&parse("'say \"hi\"'")?, &DotOptions::default(), &signature())?;
+        assert!(dot.contains("label=\"'say \\\"hi\\\"'\\nClassical\""));
+        Ok(())
+    }
+
+    #[test]
+    fn shared_subtrees_are_merged_on_request() -> anyhow::Result<()> {
+        let graph = parse("(x ^ y) & (x ^ y) | x")?;
+
+        assert_eq!(9, node_count(&to_dot(&graph, &DotOptions::default(), &signature())?));
+
+        let merged = to_dot(&graph, &DotOptions { merge_shared_subtrees: true }, &signature())?;
+        // x, y, x ^ y, &, |
+        assert_eq!(5, node_count(&merged));
+        assert!(merged.contains("n3 -> n2 [label=\"0\"]"));
+        assert!(merged.contains("n3 -> n2 [label=\"1\"]"));
+        assert!(merged.contains("n4 -> n0 [label=\"1\"]"));
+        Ok(())
+    }
+}
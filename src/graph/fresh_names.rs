@@ -0,0 +1,32 @@
+use std::collections::BTreeSet;
+
+// Generates `prefix_0`, `prefix_1`, ... skipping every name already taken
+pub(crate) struct FreshNames {
+    prefix: &'static str,
+    taken: BTreeSet<String>,
+    counter: usize,
+}
+
+impl FreshNames {
+    pub(crate) fn new(prefix: &'static str, taken: BTreeSet<String>) -> Self {
+        Self {
+            prefix,
+            taken,
+            counter: 0,
+        }
+    }
+}
+
+impl Iterator for FreshNames {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            let candidate = format!("{}_{}", self.prefix, self.counter);
+            self.counter += 1;
+            if !self.taken.contains(&candidate) {
+                return Some(candidate);
+            }
+        }
+    }
+}
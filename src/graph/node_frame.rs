@@ -1,66 +1,75 @@
 use recursion::{MappableFrame, PartiallyApplied};
 use crate::computing::{Computable, ComputingDomain};
+use crate::computing::signature::ParameterSignature;
 use crate::operations;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionParameter {
     pub identifier: String,
 }
+// A parameter the signature does not declare is Unknown here. ParameterSignature::domain_of and the other
+// functions classifying a whole graph report it as an UndeclaredParameter instead
 impl Computable for FunctionParameter {
-    fn get_domain(&self) -> ComputingDomain {
-        ComputingDomain::Quantum
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        signature.get(&self.identifier).map_or(ComputingDomain::Unknown, |declaration| declaration.domain)
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BitVec {
     pub length: usize,
     pub bit_string: String,
 }
 
 impl Computable for BitVec {
-    fn get_domain(&self) -> ComputingDomain {
+    fn get_domain(&self, _: &ParameterSignature) -> ComputingDomain {
         ComputingDomain::Quantum
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinOp<T> {
     pub operation: operations::BinaryOperation,
     pub left: T,
     pub right: T,
 }
 impl<T: Computable> Computable for BinOp<T> {
-    fn get_domain(&self) -> ComputingDomain {
-        self.left.get_domain().compare(&self.right.get_domain())
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        self.left.get_domain(signature).compare(&self.right.get_domain(signature))
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnaryOp<T> {
     pub operation: operations::UnaryOperation,
     pub operand: T,
 }
 
 impl<T: Computable> Computable for UnaryOp<T> {
-    fn get_domain(&self) -> ComputingDomain {
-        self.operand.get_domain()
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        self.operand.get_domain(signature)
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BoolOp<U> {
     pub operator: operations::BooleanOperation,
     pub operands: U
 }
 
 impl<U: Computable> Computable for BoolOp<U> {
-    fn get_domain(&self) -> ComputingDomain {
-        self.operands.get_domain()
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        self.operands.get_domain(signature)
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Compare<T, U> {
     pub left: T,
     pub operations: Vec<operations::ComparisonOperation>,
@@ -68,12 +77,13 @@ pub struct Compare<T, U> {
 }
 
 impl<T: Computable, U: Computable> Computable for Compare<T, U> {
-    fn get_domain(&self) -> ComputingDomain {
-        self.left.get_domain().compare(&self.comparators.get_domain())
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        self.left.get_domain(signature).compare(&self.comparators.get_domain(signature))
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct If<T> {
     pub condition: T,
     pub success: T,
@@ -81,38 +91,73 @@ pub struct If<T> {
 }
 
 impl<T: Computable> Computable for If<T> {
-    fn get_domain(&self) -> ComputingDomain {
-        self.condition.get_domain()
-            .compare(&self.success.get_domain())
-            .compare(&self.failure.get_domain())
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        self.condition.get_domain(signature)
+            .compare(&self.success.get_domain(signature))
+            .compare(&self.failure.get_domain(signature))
+    }
+}
+
+// Reads a quantum value out into a classical one
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Measure<T> {
+    pub operand: T,
+}
+
+impl<T: Computable> Computable for Measure<T> {
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        match self.operand.get_domain(signature) {
+            ComputingDomain::Conflict => ComputingDomain::Conflict,
+            _ => ComputingDomain::Classical,
+        }
+    }
+}
+
+// Loads a classical value into a quantum register
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Encode<T> {
+    pub operand: T,
+}
+
+impl<T: Computable> Computable for Encode<T> {
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
+        match self.operand.get_domain(signature) {
+            ComputingDomain::Conflict => ComputingDomain::Conflict,
+            _ => ComputingDomain::Quantum,
+        }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Numeric {
+    #[cfg_attr(feature = "serde", serde(with = "crate::graph::serialization::double"))]
     Double(f64),
     Int(i32),
 }
 
 impl Computable for Numeric {
-    fn get_domain(&self) -> ComputingDomain {
+    fn get_domain(&self, _: &ParameterSignature) -> ComputingDomain {
         ComputingDomain::Classical
     }
 }
 
 impl Computable for bool {
-    fn get_domain(&self) -> ComputingDomain {
+    fn get_domain(&self, _: &ParameterSignature) -> ComputingDomain {
         ComputingDomain::Classical
     }
 }
 
 impl Computable for String {
-    fn get_domain(&self) -> ComputingDomain {
+    fn get_domain(&self, _: &ParameterSignature) -> ComputingDomain {
         ComputingDomain::Classical
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeFrame<T> {
     FunctionParameter(FunctionParameter),
     NumericConstant(Numeric),
@@ -124,21 +169,25 @@ pub enum NodeFrame<T> {
     BoolOp(BoolOp<Vec<T>>),
     Compare(Compare<T, Vec<T>>),
     If(If<T>),
+    Measure(Measure<T>),
+    Encode(Encode<T>),
 }
 
 impl<T: Computable> Computable for NodeFrame<T> {
-    fn get_domain(&self) -> ComputingDomain {
+    fn get_domain(&self, signature: &ParameterSignature) -> ComputingDomain {
         match self {
-            NodeFrame::FunctionParameter(f) => f.get_domain(),
-            NodeFrame::NumericConstant(n) => n.get_domain(),
-            NodeFrame::StringConstant(s) => s.get_domain(),
-            NodeFrame::BooleanConstant(b) => b.get_domain(),
-            NodeFrame::BitVec(bv) => bv.get_domain(),
-            NodeFrame::BinOp(bin) => bin.get_domain(),
-            NodeFrame::UnaryOp(unary) => unary.get_domain(),
-            NodeFrame::BoolOp(bool_op) => bool_op.get_domain(),
-            NodeFrame::Compare(cmp) => cmp.get_domain(),
-            NodeFrame::If(if_node) => if_node.get_domain(),
+            NodeFrame::FunctionParameter(f) => f.get_domain(signature),
+            NodeFrame::NumericConstant(n) => n.get_domain(signature),
+            NodeFrame::StringConstant(s) => s.get_domain(signature),
+            NodeFrame::BooleanConstant(b) => b.get_domain(signature),
+            NodeFrame::BitVec(bv) => bv.get_domain(signature),
+            NodeFrame::BinOp(bin) => bin.get_domain(signature),
+            NodeFrame::UnaryOp(unary) => unary.get_domain(signature),
+            NodeFrame::BoolOp(bool_op) => bool_op.get_domain(signature),
+            NodeFrame::Compare(cmp) => cmp.get_domain(signature),
+            NodeFrame::If(if_node) => if_node.get_domain(signature),
+            NodeFrame::Measure(measure) => measure.get_domain(signature),
+            NodeFrame::Encode(encode) => encode.get_domain(signature),
         }
     }
 }
@@ -194,6 +243,8 @@ impl MappableFrame for NodeFrame<PartiallyApplied> {
                     failure: f(if_node.failure),
                 })
             }
+            NodeFrame::Measure(measure) => NodeFrame::Measure(Measure { operand: f(measure.operand) }),
+            NodeFrame::Encode(encode) => NodeFrame::Encode(Encode { operand: f(encode.operand) }),
         }
     }
 }
@@ -239,7 +290,117 @@ impl<T> NodeFrame<T> {
                     failure: f(if_node.failure),
                 })
             }
+            NodeFrame::Measure(measure) => NodeFrame::Measure(Measure { operand: f(measure.operand) }),
+            NodeFrame::Encode(encode) => NodeFrame::Encode(Encode { operand: f(encode.operand) }),
+        }
+    }
+
+    pub fn try_map<A, E, F: FnMut(T) -> Result<A, E>>(self, mut f: F) -> Result<NodeFrame<A>, E> {
+        Ok(match self {
+            NodeFrame::FunctionParameter(param) => NodeFrame::FunctionParameter(param),
+            NodeFrame::NumericConstant(numeric) => NodeFrame::NumericConstant(numeric),
+            NodeFrame::StringConstant(string) => NodeFrame::StringConstant(string),
+            NodeFrame::BooleanConstant(boolean) => NodeFrame::BooleanConstant(boolean),
+            NodeFrame::BitVec(bitvec) => NodeFrame::BitVec(bitvec),
+            NodeFrame::BinOp(binop) => {
+                NodeFrame::BinOp(BinOp {
+                    operation: binop.operation,
+                    left: f(binop.left)?,
+                    right: f(binop.right)?,
+                })
+            }
+            NodeFrame::UnaryOp(unaryop) => {
+                NodeFrame::UnaryOp(UnaryOp {
+                    operation: unaryop.operation,
+                    operand: f(unaryop.operand)?,
+                })
+            }
+            NodeFrame::BoolOp(boolop) => {
+                NodeFrame::BoolOp(BoolOp {
+                    operator: boolop.operator,
+                    operands: boolop.operands.into_iter().map(f).collect::<Result<_, _>>()?,
+                })
+            }
+            NodeFrame::Compare(compare) => {
+                NodeFrame::Compare(Compare {
+                    left: f(compare.left)?,
+                    operations: compare.operations,
+                    comparators: compare.comparators.into_iter().map(f).collect::<Result<_, _>>()?,
+                })
+            }
+            NodeFrame::If(if_node) => {
+                NodeFrame::If(If {
+                    condition: f(if_node.condition)?,
+                    success: f(if_node.success)?,
+                    failure: f(if_node.failure)?,
+                })
+            }
+            NodeFrame::Measure(measure) => NodeFrame::Measure(Measure { operand: f(measure.operand)? }),
+            NodeFrame::Encode(encode) => NodeFrame::Encode(Encode { operand: f(encode.operand)? }),
+        })
+    }
+
+    // Borrows the children, the payload of leaf nodes is cloned
+    pub fn as_ref(&self) -> NodeFrame<&T> {
+        match self {
+            NodeFrame::FunctionParameter(param) => NodeFrame::FunctionParameter(param.clone()),
+            NodeFrame::NumericConstant(numeric) => NodeFrame::NumericConstant(numeric.clone()),
+            NodeFrame::StringConstant(string) => NodeFrame::StringConstant(string.clone()),
+            NodeFrame::BooleanConstant(boolean) => NodeFrame::BooleanConstant(*boolean),
+            NodeFrame::BitVec(bitvec) => NodeFrame::BitVec(bitvec.clone()),
+            NodeFrame::BinOp(binop) => {
+                NodeFrame::BinOp(BinOp {
+                    operation: binop.operation,
+                    left: &binop.left,
+                    right: &binop.right,
+                })
+            }
+            NodeFrame::UnaryOp(unaryop) => {
+                NodeFrame::UnaryOp(UnaryOp {
+                    operation: unaryop.operation,
+                    operand: &unaryop.operand,
+                })
+            }
+            NodeFrame::BoolOp(boolop) => {
+                NodeFrame::BoolOp(BoolOp {
+                    operator: boolop.operator,
+                    operands: boolop.operands.iter().collect(),
+                })
+            }
+            NodeFrame::Compare(compare) => {
+                NodeFrame::Compare(Compare {
+                    left: &compare.left,
+                    operations: compare.operations.clone(),
+                    comparators: compare.comparators.iter().collect(),
+                })
+            }
+            NodeFrame::If(if_node) => {
+                NodeFrame::If(If {
+                    condition: &if_node.condition,
+                    success: &if_node.success,
+                    failure: &if_node.failure,
+                })
+            }
+            NodeFrame::Measure(measure) => NodeFrame::Measure(Measure { operand: &measure.operand }),
+            NodeFrame::Encode(encode) => NodeFrame::Encode(Encode { operand: &encode.operand }),
         }
     }
 
+    // Children in the same order map visits them
+    pub fn children(&self) -> Vec<&T> {
+        match self {
+            NodeFrame::FunctionParameter(_)
+            | NodeFrame::NumericConstant(_)
+            | NodeFrame::StringConstant(_)
+            | NodeFrame::BooleanConstant(_)
+            | NodeFrame::BitVec(_) => Vec::new(),
+            NodeFrame::BinOp(binop) => vec![&binop.left, &binop.right],
+            NodeFrame::UnaryOp(unaryop) => vec![&unaryop.operand],
+            NodeFrame::BoolOp(boolop) => boolop.operands.iter().collect(),
+            NodeFrame::Compare(compare) => std::iter::once(&compare.left).chain(&compare.comparators).collect(),
+            NodeFrame::If(if_node) => vec![&if_node.condition, &if_node.success, &if_node.failure],
+            NodeFrame::Measure(measure) => vec![&measure.operand],
+            NodeFrame::Encode(encode) => vec![&encode.operand],
+        }
+    }
 }
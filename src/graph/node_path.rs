@@ -0,0 +1,127 @@
+use std::fmt::{Display, Formatter};
+use recursion::{expand_and_collapse, MappableFrame, PartiallyApplied};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::NodeFrame;
+
+// A path is the list of child indices taken from the root to reach a node.
+// Children are numbered in the same order NodeFrame::map visits them:
+// BinOp is (left, right), Compare is (left, comparators...) and If is (condition, success, failure)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodePath {
+    indices: Vec<usize>,
+}
+
+impl NodePath {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn from_indices(indices: Vec<usize>) -> Self {
+        Self { indices }
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        let (_, indices) = self.indices.split_last()?;
+        Some(Self { indices: indices.to_vec() })
+    }
+
+    pub fn child(&self, index: usize) -> Self {
+        let mut indices = self.indices.clone();
+        indices.push(index);
+        Self { indices }
+    }
+
+    // Used while an error bubbles up from a child to its parent
+    pub(crate) fn prepend(&mut self, index: usize) {
+        self.indices.insert(0, index);
+    }
+
+    pub fn resolve<'a>(&self, node: &'a BoxedNode) -> Option<&'a BoxedNode> {
+        self.indices.iter().try_fold(node, |current, index| {
+            current.data.children().into_iter().nth(*index).map(|child| child.as_ref())
+        })
+    }
+}
+
+impl Display for NodePath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.indices.is_empty() {
+            return write!(f, "/");
+        }
+        for index in &self.indices {
+            write!(f, "/{index}")?;
+        }
+        Ok(())
+    }
+}
+
+// A NodeFrame tagged with the path of the node it was taken from
+pub(crate) struct PathFrame<X> {
+    pub path: NodePath,
+    pub frame: NodeFrame<X>,
+}
+
+impl MappableFrame for PathFrame<PartiallyApplied> {
+    type Frame<X> = PathFrame<X>;
+
+    fn map_frame<A, B>(input: Self::Frame<A>, f: impl FnMut(A) -> B) -> Self::Frame<B> {
+        PathFrame {
+            path: input.path,
+            frame: input.frame.map(f),
+        }
+    }
+}
+
+// Like collapse_frames, but every frame also knows where in the tree it is
+pub(crate) fn collapse_with_paths<Out>(node: &BoxedNode, collapse: impl FnMut(PathFrame<Out>) -> Out) -> Out {
+    expand_and_collapse::<PathFrame<PartiallyApplied>, (&BoxedNode, NodePath), Out>(
+        (node, NodePath::root()),
+        |(node, path)| {
+            let mut index = 0;
+            let frame = node.data.as_ref().map(|child| {
+                let child_path = path.child(index);
+                index += 1;
+                (child.as_ref(), child_path)
+            });
+            PathFrame { path, frame }
+        },
+        collapse,
+    )
+}
+
+#[cfg(test)]
+mod node_path_tests {
+    use super::*;
+    use crate::graph::node_frame::{BinOp, NodeFrame, Numeric, UnaryOp};
+    use crate::operations::{BinaryOperation, UnaryOperation};
+
+    #[test]
+    fn resolve_follows_child_indices() {
+        let graph = BoxedNode {
+            data: NodeFrame::BinOp(BinOp {
+                operation: BinaryOperation::Add,
+                left: Box::new(BoxedNode { data: NodeFrame::NumericConstant(Numeric::Int(1)) }),
+                right: Box::new(BoxedNode {
+                    data: NodeFrame::UnaryOp(UnaryOp {
+                        operation: UnaryOperation::UnaryMinus,
+                        operand: Box::new(BoxedNode { data: NodeFrame::NumericConstant(Numeric::Int(2)) }),
+                    })
+                }),
+            })
+        };
+
+        let path = NodePath::root().child(1).child(0);
+        assert!(matches!(path.resolve(&graph).map(|n| &n.data), Some(NodeFrame::NumericConstant(Numeric::Int(2)))));
+        assert!(NodePath::root().child(2).resolve(&graph).is_none());
+        assert_eq!("/1/0", path.to_string());
+        assert_eq!("/", NodePath::root().to_string());
+    }
+}
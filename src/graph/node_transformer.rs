@@ -1,11 +1,7 @@
 use recursion::CollapsibleExt;
 use crate::graph::boxed_nodes::BoxedNode;
-use crate::graph::node_frame::{BinOp, BitVec, BoolOp, Compare, FunctionParameter, If, NodeFrame, Numeric, UnaryOp};
-
-enum Value {
-    Simplified,
-    Unsimplified(Box<NodeFrame<Value>>),
-}
+use crate::graph::node_frame::{BinOp, BitVec, BoolOp, Compare, Encode, FunctionParameter, If, Measure, NodeFrame, Numeric, UnaryOp};
+use crate::graph::transform_error::TransformError;
 
 pub trait NodeTransformer {
     fn transform_function_parameter(&mut self, parameter: FunctionParameter) -> Option<BoxedNode> {
@@ -92,61 +88,184 @@ pub trait NodeTransformer {
         })
     }
 
+    fn transform_measure(&mut self, parameter: Measure<Option<BoxedNode>>) -> Option<BoxedNode> {
+        Some(BoxedNode {
+            data: NodeFrame::Measure(Measure {
+                operand: Box::new(parameter.operand?),
+            })
+        })
+    }
+
+    fn transform_encode(&mut self, parameter: Encode<Option<BoxedNode>>) -> Option<BoxedNode> {
+        Some(BoxedNode {
+            data: NodeFrame::Encode(Encode {
+                operand: Box::new(parameter.operand?),
+            })
+        })
+    }
+
     fn transform_node(&mut self, node: BoxedNode) -> Option<BoxedNode> {
         self.default_visit(node)
     }
     fn default_visit(&mut self, node: BoxedNode) -> Option<BoxedNode> {
-        node.collapse_frames(|x| {
-            match x {
-                NodeFrame::FunctionParameter(p) => self.transform_function_parameter(p),
-                NodeFrame::NumericConstant(n) => self.transform_numeric_constant(n),
-                NodeFrame::StringConstant(s) => self.transform_string_constant(s),
-                NodeFrame::BooleanConstant(b) => self.transform_boolean_constant(b),
-                NodeFrame::BitVec(bv) => self.transform_bitvec(bv),
-                NodeFrame::BinOp(binop) => self.transform_binary_operation(binop),
-                NodeFrame::UnaryOp(unaryop) => self.transform_unary_operation(unaryop),
-                NodeFrame::BoolOp(boolop) => self.transform_boolean_operation(boolop),
-                NodeFrame::Compare(compareop) => self.transform_comparison(compareop),
-                NodeFrame::If(ifnode) => self.transform_if(ifnode),
-            }
-        })
+        node.collapse_frames(|x| self.transform_frame(x))
     }
-}
 
-fn drop_non_vals(node: BoxedNode) {
-    let x = node.collapse_frames(|x| {
-        match x {
-            NodeFrame::FunctionParameter(_) => Value::Simplified,
-            NodeFrame::NumericConstant(_) => Value::Simplified,
-            NodeFrame::StringConstant(_) => Value::Simplified,
-            NodeFrame::BooleanConstant(_) => Value::Simplified,
-            NodeFrame::BitVec(_) => Value::Simplified,
-            NodeFrame::BinOp(binop) => {
-                Value::Unsimplified(Box::new(NodeFrame::BinOp(binop)))
-            }
-            NodeFrame::UnaryOp(a) => {
-                Value::Unsimplified(Box::new(NodeFrame::UnaryOp(a)))
-            }
-            NodeFrame::BoolOp(_) => {todo!()}
-            NodeFrame::Compare(_) => {todo!()}
-            NodeFrame::If(_) => {todo!()}
+    // Transforms a single node whose children were already transformed
+    fn transform_frame(&mut self, frame: NodeFrame<Option<BoxedNode>>) -> Option<BoxedNode> {
+        match frame {
+            NodeFrame::FunctionParameter(p) => self.transform_function_parameter(p),
+            NodeFrame::NumericConstant(n) => self.transform_numeric_constant(n),
+            NodeFrame::StringConstant(s) => self.transform_string_constant(s),
+            NodeFrame::BooleanConstant(b) => self.transform_boolean_constant(b),
+            NodeFrame::BitVec(bv) => self.transform_bitvec(bv),
+            NodeFrame::BinOp(binop) => self.transform_binary_operation(binop),
+            NodeFrame::UnaryOp(unaryop) => self.transform_unary_operation(unaryop),
+            NodeFrame::BoolOp(boolop) => self.transform_boolean_operation(boolop),
+            NodeFrame::Compare(compareop) => self.transform_comparison(compareop),
+            NodeFrame::If(ifnode) => self.transform_if(ifnode),
+            NodeFrame::Measure(measure) => self.transform_measure(measure),
+            NodeFrame::Encode(encode) => self.transform_encode(encode),
         }
-    });
-    match x {
-        Value::Simplified => {}
-        Value::Unsimplified(_) => {}
     }
 }
 
+// Fallible counterpart of NodeTransformer.
+// Children are always present, so a transformation either produces a node or explains why it could not.
+// Errors raised by a transform_* method are reported for the node being transformed, the visit extends
+// their path with the child indices while they bubble up to the root
+pub trait TryNodeTransformer {
+    fn try_transform_function_parameter(&mut self, parameter: FunctionParameter) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::FunctionParameter(parameter),
+        })
+    }
+
+    fn try_transform_numeric_constant(&mut self, parameter: Numeric) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::NumericConstant(parameter),
+        })
+    }
+
+    fn try_transform_string_constant(&mut self, parameter: String) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::StringConstant(parameter),
+        })
+    }
+
+    fn try_transform_boolean_constant(&mut self, parameter: bool) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::BooleanConstant(parameter),
+        })
+    }
+
+    fn try_transform_bitvec(&mut self, parameter: BitVec) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::BitVec(parameter),
+        })
+    }
+
+    fn try_transform_binary_operation(&mut self, parameter: BinOp<BoxedNode>) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::BinOp(BinOp {
+                operation: parameter.operation,
+                left: Box::new(parameter.left),
+                right: Box::new(parameter.right),
+            }),
+        })
+    }
+
+    fn try_transform_unary_operation(&mut self, parameter: UnaryOp<BoxedNode>) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::UnaryOp(UnaryOp {
+                operation: parameter.operation,
+                operand: Box::new(parameter.operand),
+            }),
+        })
+    }
+
+    fn try_transform_boolean_operation(&mut self, parameter: BoolOp<Vec<BoxedNode>>) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::BoolOp(BoolOp {
+                operator: parameter.operator,
+                operands: parameter.operands.into_iter().map(Box::new).collect(),
+            })
+        })
+    }
+
+    fn try_transform_comparison(&mut self, parameter: Compare<BoxedNode, Vec<BoxedNode>>) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::Compare(Compare {
+                left: Box::new(parameter.left),
+                operations: parameter.operations,
+                comparators: parameter.comparators.into_iter().map(Box::new).collect(),
+            })
+        })
+    }
 
+    fn try_transform_if(&mut self, parameter: If<BoxedNode>) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::If(If {
+                condition: Box::new(parameter.condition),
+                success: Box::new(parameter.success),
+                failure: Box::new(parameter.failure),
+            })
+        })
+    }
+
+    fn try_transform_measure(&mut self, parameter: Measure<BoxedNode>) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::Measure(Measure {
+                operand: Box::new(parameter.operand),
+            })
+        })
+    }
+
+    fn try_transform_encode(&mut self, parameter: Encode<BoxedNode>) -> Result<BoxedNode, TransformError> {
+        Ok(BoxedNode {
+            data: NodeFrame::Encode(Encode {
+                operand: Box::new(parameter.operand),
+            })
+        })
+    }
+
+    fn try_transform_node(&mut self, node: BoxedNode) -> Result<BoxedNode, TransformError> {
+        self.try_default_visit(node)
+    }
+
+    fn try_default_visit(&mut self, node: BoxedNode) -> Result<BoxedNode, TransformError> {
+        node.collapse_frames(|x: NodeFrame<Result<BoxedNode, TransformError>>| {
+            let mut index = 0;
+            let frame = x.try_map(|child| {
+                let child = child.map_err(|e| e.nested_in(index));
+                index += 1;
+                child
+            })?;
+            match frame {
+                NodeFrame::FunctionParameter(p) => self.try_transform_function_parameter(p),
+                NodeFrame::NumericConstant(n) => self.try_transform_numeric_constant(n),
+                NodeFrame::StringConstant(s) => self.try_transform_string_constant(s),
+                NodeFrame::BooleanConstant(b) => self.try_transform_boolean_constant(b),
+                NodeFrame::BitVec(bv) => self.try_transform_bitvec(bv),
+                NodeFrame::BinOp(binop) => self.try_transform_binary_operation(binop),
+                NodeFrame::UnaryOp(unaryop) => self.try_transform_unary_operation(unaryop),
+                NodeFrame::BoolOp(boolop) => self.try_transform_boolean_operation(boolop),
+                NodeFrame::Compare(compareop) => self.try_transform_comparison(compareop),
+                NodeFrame::If(ifnode) => self.try_transform_if(ifnode),
+                NodeFrame::Measure(measure) => self.try_transform_measure(measure),
+                NodeFrame::Encode(encode) => self.try_transform_encode(encode),
+            }
+        })
+    }
+}
 
 #[cfg(test)]
 mod transformer_tests {
-    use recursion::{Collapsible, Expandable};
     use crate::operations::BooleanOperation;
+    use crate::graph::node_path::NodePath;
+    use crate::graph::transform_error::TransformErrorKind;
     use super::*;
     use anyhow::Context;
-    use crate::graph::structure_key::{StructuralIdentifier, StructureKey};
 
     //
     //  Macro syntax should probably look like this:
@@ -196,4 +315,49 @@ mod transformer_tests {
         assert_eq!(expected_structure, actual_structure);
         Ok(())
     }
+
+    impl TryNodeTransformer for DefaultImplementor {}
+
+    struct RejectFalse {}
+    impl TryNodeTransformer for RejectFalse {
+        fn try_transform_boolean_constant(&mut self, parameter: bool) -> Result<BoxedNode, TransformError> {
+            if parameter {
+                Ok(bool_const!(parameter))
+            } else {
+                Err(TransformErrorKind::UnsupportedOperation("false".to_string()).into())
+            }
+        }
+    }
+
+    #[test]
+    fn default_fallible_implementation_does_not_mutate_structure() -> anyhow::Result<()> {
+        let macro_graph: BoxedNode = bool_op!(BooleanOperation::And,
+            bool_const!(true),
+            bool_op!(BooleanOperation::Or,
+                bool_const!(false),
+                bool_const!(true)
+            )
+        );
+        let expected_structure = macro_graph.clone().get_structure_key();
+        let mut default_implementor = DefaultImplementor {};
+
+        let actual_structure = default_implementor.try_transform_node(macro_graph)?.get_structure_key();
+        assert_eq!(expected_structure, actual_structure);
+        Ok(())
+    }
+
+    #[test]
+    fn fallible_errors_carry_the_offending_path() {
+        let macro_graph: BoxedNode = bool_op!(BooleanOperation::And,
+            bool_const!(true),
+            bool_op!(BooleanOperation::Or,
+                bool_const!(true),
+                bool_const!(false)
+            )
+        );
+
+        let error = RejectFalse {}.try_transform_node(macro_graph.clone()).expect_err("false should be rejected");
+        assert_eq!(NodePath::from_indices(vec![1, 1]), error.path);
+        assert!(matches!(error.path.resolve(&macro_graph).map(|n| &n.data), Some(NodeFrame::BooleanConstant(false))));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,290 @@
+use std::fmt::{Display, Formatter};
+use recursion::CollapsibleExt;
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{NodeFrame, Numeric};
+use crate::operations::{BooleanOperation, Precedence, UnaryOperation};
+
+// Renders a graph as a Python expression with as few parentheses as the operator precedence allows.
+// The output parses back into the same structure, with a few exceptions that have no Python spelling:
+// non finite doubles, BoolOps with less than two operands and BitVecs whose length disagrees with their bits
+impl Display for BoxedNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rendered = self.collapse_frames(render_frame);
+        write!(f, "{}", rendered.text)
+    }
+}
+
+impl Display for NodeFrame<Box<BoxedNode>> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let rendered = render_frame(self.as_ref().map(|child| child.as_ref().collapse_frames(render_frame)));
+        write!(f, "{}", rendered.text)
+    }
+}
+
+struct Rendered {
+    text: String,
+    precedence: Precedence,
+    // `-3` is read back as a negative constant, so negating a positive constant needs parentheses
+    is_unsigned_number: bool,
+}
+
+impl Rendered {
+    fn atom(text: String) -> Self {
+        Self {
+            text,
+            precedence: Precedence::Atom,
+            is_unsigned_number: false,
+        }
+    }
+
+    fn operation(text: String, precedence: Precedence) -> Self {
+        Self {
+            text,
+            precedence,
+            is_unsigned_number: false,
+        }
+    }
+
+    fn wrapped_if(self, parenthesize: bool) -> String {
+        if parenthesize {
+            format!("({})", self.text)
+        } else {
+            self.text
+        }
+    }
+
+    // Operand of an operator with the given precedence that does not chain with itself
+    fn tighter_than(self, precedence: Precedence) -> String {
+        let parenthesize = self.precedence <= precedence;
+        self.wrapped_if(parenthesize)
+    }
+
+    fn at_least(self, precedence: Precedence) -> String {
+        let parenthesize = self.precedence < precedence;
+        self.wrapped_if(parenthesize)
+    }
+}
+
+fn render_frame(frame: NodeFrame<Rendered>) -> Rendered {
+    match frame {
+        NodeFrame::FunctionParameter(p) => Rendered::atom(p.identifier),
+        NodeFrame::NumericConstant(n) => render_numeric(&n),
+        NodeFrame::StringConstant(s) => Rendered::atom(render_string(&s)),
+        NodeFrame::BooleanConstant(b) => Rendered::atom(if b { "True" } else { "False" }.to_string()),
+        NodeFrame::BitVec(bv) => Rendered::atom(format!("0b{}", bv.bit_string)),
+        NodeFrame::BinOp(binop) => {
+            // Every binary operator is left associative
+            let precedence = binop.operation.precedence();
+            let text = format!("{} {} {}", binop.left.at_least(precedence), binop.operation.symbol(), binop.right.tighter_than(precedence));
+            Rendered::operation(text, precedence)
+        }
+        NodeFrame::UnaryOp(unaryop) => {
+            let precedence = unaryop.operation.precedence();
+            let text = match unaryop.operation {
+                UnaryOperation::Not => format!("not {}", unaryop.operand.at_least(precedence)),
+                UnaryOperation::UnaryMinus if unaryop.operand.is_unsigned_number => format!("-({})", unaryop.operand.text),
+                operation => format!("{}{}", operation.symbol(), unaryop.operand.at_least(precedence)),
+            };
+            Rendered::operation(text, precedence)
+        }
+        NodeFrame::BoolOp(boolop) => {
+            if boolop.operands.len() < 2 {
+                return render_degenerate_bool_op(boolop.operator, boolop.operands);
+            }
+            // A nested BoolOp with the same operator keeps its parentheses, otherwise it would be flattened into this one
+            let precedence = boolop.operator.precedence();
+            let separator = format!(" {} ", boolop.operator.symbol());
+            let operands: Vec<String> = boolop.operands.into_iter().map(|operand| operand.tighter_than(precedence)).collect();
+            Rendered::operation(operands.join(&separator), precedence)
+        }
+        NodeFrame::Compare(compare) => {
+            // Comparisons chain, so a nested comparison always needs parentheses
+            let mut text = compare.left.tighter_than(Precedence::Comparison);
+            for (operation, comparator) in compare.operations.iter().zip(compare.comparators) {
+                text = format!("{text} {} {}", operation.symbol(), comparator.tighter_than(Precedence::Comparison));
+            }
+            Rendered::operation(text, Precedence::Comparison)
+        }
+        NodeFrame::If(if_node) => {
+            let text = format!("{} if {} else {}",
+                if_node.success.tighter_than(Precedence::Conditional),
+                if_node.condition.tighter_than(Precedence::Conditional),
+                if_node.failure.at_least(Precedence::Conditional),
+            );
+            Rendered::operation(text, Precedence::Conditional)
+        }
+        // Boundaries are spelled as calls, which bind like atoms
+        NodeFrame::Measure(measure) => Rendered::atom(format!("measure({})", measure.operand.text)),
+        NodeFrame::Encode(encode) => Rendered::atom(format!("encode({})", encode.operand.text)),
+    }
+}
+
+// An empty BoolOp evaluates to its identity, a single operand BoolOp to the operand itself
+fn render_degenerate_bool_op(operator: BooleanOperation, mut operands: Vec<Rendered>) -> Rendered {
+    match operands.pop() {
+        Some(operand) => operand,
+        None => Rendered::atom(match operator {
+            BooleanOperation::And => "True",
+            BooleanOperation::Or => "False",
+        }.to_string()),
+    }
+}
+
+fn render_numeric(numeric: &Numeric) -> Rendered {
+    let (text, is_negative) = match numeric {
+        Numeric::Int(i) => (i.to_string(), *i < 0),
+        Numeric::Double(d) if d.is_nan() => ("nan".to_string(), false),
+        Numeric::Double(d) if d.is_infinite() => (if *d > 0.0 { "inf" } else { "-inf" }.to_string(), *d < 0.0),
+        // Debug always keeps a decimal point or an exponent, so the text reads back as a double
+        Numeric::Double(d) => (format!("{d:?}"), d.is_sign_negative()),
+    };
+    Rendered {
+        text,
+        precedence: if is_negative { Precedence::Unary } else { Precedence::Atom },
+        is_unsigned_number: !is_negative,
+    }
+}
+
+fn render_string(string: &str) -> String {
+    let mut text = String::with_capacity(string.len() + 2);
+    text.push('\'');
+    for character in string.chars() {
+        match character {
+            '\\' => text.push_str("\\\\"),
+            '\'' => text.push_str("\\'"),
+            '\n' => text.push_str("\\n"),
+            '\t' => text.push_str("\\t"),
+            '\r' => text.push_str("\\r"),
+            '\0' => text.push_str("\\0"),
+            c => text.push(c),
+        }
+    }
+    text.push('\'');
+    text
+}
+
+#[cfg(test)]
+mod printer_tests {
+    use super::*;
+    use crate::graph::node_frame::{BinOp, BitVec, BoolOp, Compare, Encode, FunctionParameter, If, Measure, UnaryOp};
+    use crate::operations::{BinaryOperation, ComparisonOperation};
+    use crate::parser::parse;
+
+    fn assert_prints_unchanged(source: &str) -> anyhow::Result<()> {
+        assert_eq!(source, parse(source)?.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn minimal_parentheses() -> anyhow::Result<()> {
+        assert_prints_unchanged("1 + 2 * x")?;
+        assert_prints_unchanged("(1 + 2) * x")?;
+        assert_prints_unchanged("a - (b - c)")?;
+        assert_prints_unchanged("a - b - c")?;
+        assert_prints_unchanged("(a | b) & c")?;
+        assert_prints_unchanged("-(a + b) << ~c")?;
+        assert_prints_unchanged("not a and (b or c)")?;
+        assert_prints_unchanged("a or b and c")?;
+        assert_prints_unchanged("(a or b) or c")?;
+        assert_prints_unchanged("not a < b <= c")?;
+        assert_prints_unchanged("(a < b) == c")?;
+        assert_prints_unchanged("a not in b is not c")?;
+        assert_prints_unchanged("(a if b else c) if d else e if f else g")?;
+        assert_prints_unchanged("(not a) + 1")
+    }
+
+    #[test]
+    fn literals() -> anyhow::Result<()> {
+        assert_prints_unchanged("'it\\'s' + 'a\\nb'")?;
+        assert_prints_unchanged("0.5 * -2 - -(3)")?;
+        assert_prints_unchanged("1e-7 + 0b0010")?;
+        assert_prints_unchanged("True is not False")?;
+        assert_prints_unchanged("-measure(q + 1) * encode(x if c else 0)")
+    }
+
+    #[test]
+    fn frames_render_like_nodes() -> anyhow::Result<()> {
+        let node = parse("(a + 1) * b")?;
+        assert_eq!(node.to_string(), node.data.to_string());
+        Ok(())
+    }
+
+    // Small deterministic generator, so the property test does not need an extra dependency
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+            items[self.next(items.len())]
+        }
+    }
+
+    fn random_node(random: &mut Random, depth: usize) -> BoxedNode {
+        let boxed = |random: &mut Random| Box::new(random_node(random, depth - 1));
+        let choice = if depth == 0 { random.next(6) } else { random.next(13) };
+        let data = match choice {
+            0 => NodeFrame::FunctionParameter(FunctionParameter { identifier: random.pick(&["x", "y", "angle", "_q0"]).to_string() }),
+            1 => NodeFrame::NumericConstant(Numeric::Int(random.pick(&[0, 3, -7, i32::MIN, i32::MAX]))),
+            2 => NodeFrame::NumericConstant(Numeric::Double(random.pick(&[0.5, -2.25, 1e-9, 3e20, -0.0]))),
+            3 => NodeFrame::StringConstant(random.pick(&["", "a'b", "\\n", "tab\there"]).to_string()),
+            4 => NodeFrame::BooleanConstant(random.next(2) == 0),
+            5 => NodeFrame::BitVec(BitVec { length: 3, bit_string: "010".to_string() }),
+            6 => NodeFrame::BinOp(BinOp {
+                operation: random.pick(&[
+                    BinaryOperation::Add, BinaryOperation::Subtract, BinaryOperation::Divide,
+                    BinaryOperation::Multiply, BinaryOperation::BitwiseAnd, BinaryOperation::BitwiseLeftShift,
+                    BinaryOperation::BitwiseRightShift, BinaryOperation::BitwiseOr, BinaryOperation::BitwiseXor,
+                ]),
+                left: boxed(random),
+                right: boxed(random),
+            }),
+            7 => NodeFrame::UnaryOp(UnaryOp {
+                operation: random.pick(&[UnaryOperation::Not, UnaryOperation::Invert, UnaryOperation::UnaryMinus]),
+                operand: boxed(random),
+            }),
+            8 => NodeFrame::BoolOp(BoolOp {
+                operator: random.pick(&[BooleanOperation::And, BooleanOperation::Or]),
+                operands: (0..2 + random.next(2)).map(|_| boxed(random)).collect(),
+            }),
+            9 => {
+                let operations: Vec<ComparisonOperation> = (0..1 + random.next(3)).map(|_| random.pick(&[
+                    ComparisonOperation::Equal, ComparisonOperation::GreaterThan, ComparisonOperation::GreaterThanOrEqual,
+                    ComparisonOperation::In, ComparisonOperation::Is, ComparisonOperation::IsNot,
+                    ComparisonOperation::LessThan, ComparisonOperation::LessThanOrEqual,
+                    ComparisonOperation::NotEqual, ComparisonOperation::NotIn,
+                ])).collect();
+                NodeFrame::Compare(Compare {
+                    left: boxed(random),
+                    comparators: operations.iter().map(|_| boxed(random)).collect(),
+                    operations,
+                })
+            }
+            10 => NodeFrame::Measure(Measure { operand: boxed(random) }),
+            11 => NodeFrame::Encode(Encode { operand: boxed(random) }),
+            _ => NodeFrame::If(If {
+                condition: boxed(random),
+                success: boxed(random),
+                failure: boxed(random),
+            }),
+        };
+        BoxedNode { data }
+    }
+
+    #[test]
+    fn round_trip_preserves_structure() -> anyhow::Result<()> {
+        let mut random = Random(0x9e3779b97f4a7c15);
+        for _ in 0..2000 {
+            let node = random_node(&mut random, 5);
+            let source = node.to_string();
+            let reparsed = parse(&source).map_err(|e| anyhow::anyhow!("{e} while parsing {source:?}"))?;
+            assert_eq!(node.get_structure_key(), reparsed.get_structure_key(), "round trip of {source:?}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,317 @@
+// A BoxedNode is serialized as a flat, versioned list of frames instead of nested objects,
+// and a StructureKey as a flat list of identifiers, so neither serializing nor deserializing
+// recurses once per level of the tree. The schema is described in the README
+use recursion::CollapsibleExt;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::NodeFrame;
+use crate::graph::structure_key::{StructuralIdentifier, StructureKey};
+
+// Version 2 added the Measure and Encode nodes
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct SerializedGraph {
+    version: u32,
+    // Children are referenced by their index in this list and always precede their parent,
+    // which makes the last node the root
+    nodes: Vec<NodeFrame<usize>>,
+}
+
+impl Serialize for BoxedNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut nodes = Vec::new();
+        self.collapse_frames(|frame: NodeFrame<usize>| {
+            nodes.push(frame);
+            nodes.len() - 1
+        });
+        SerializedGraph {
+            version: SCHEMA_VERSION,
+            nodes,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BoxedNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let graph = SerializedGraph::deserialize(deserializer)?;
+        if graph.version != SCHEMA_VERSION {
+            return Err(D::Error::custom(format!(
+                "unsupported graph schema version {}, expected {SCHEMA_VERSION}", graph.version
+            )));
+        }
+        build_tree(graph.nodes).map_err(D::Error::custom)
+    }
+}
+
+fn build_tree(nodes: Vec<NodeFrame<usize>>) -> Result<BoxedNode, String> {
+    let mut built: Vec<Option<BoxedNode>> = Vec::with_capacity(nodes.len());
+    for (index, frame) in nodes.into_iter().enumerate() {
+        let data = frame.try_map(|child| {
+            if child >= index {
+                return Err(format!("node {index} refers to node {child}, which does not precede it"));
+            }
+            built[child].take()
+                .map(Box::new)
+                .ok_or_else(|| format!("node {child} is used more than once"))
+        })?;
+        built.push(Some(BoxedNode { data }));
+    }
+
+    let root = built.pop().flatten().ok_or_else(|| "the graph has no nodes".to_string())?;
+    if let Some(unused) = built.iter().position(Option::is_some) {
+        return Err(format!("node {unused} is not connected to the root"));
+    }
+    Ok(root)
+}
+
+// Doubles are written as JSON numbers, which cannot hold NaN or the infinities,
+// so those are written as the strings "NaN", "inf" and "-inf"
+pub(crate) mod double {
+    use std::fmt::Formatter;
+    use serde::de::{Error, Visitor};
+    use serde::{Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        match *value {
+            value if value.is_nan() => serializer.serialize_str("NaN"),
+            f64::INFINITY => serializer.serialize_str("inf"),
+            f64::NEG_INFINITY => serializer.serialize_str("-inf"),
+            value => serializer.serialize_f64(value),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        deserializer.deserialize_any(DoubleVisitor)
+    }
+
+    struct DoubleVisitor;
+
+    impl Visitor<'_> for DoubleVisitor {
+        type Value = f64;
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            formatter.write_str("a number, \"NaN\", \"inf\" or \"-inf\"")
+        }
+
+        fn visit_f64<E: Error>(self, value: f64) -> Result<f64, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: Error>(self, value: i64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_u64<E: Error>(self, value: u64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_str<E: Error>(self, value: &str) -> Result<f64, E> {
+            match value {
+                "NaN" => Ok(f64::NAN),
+                "inf" => Ok(f64::INFINITY),
+                "-inf" => Ok(f64::NEG_INFINITY),
+                _ => Err(E::custom(format!("{value:?} is not a double"))),
+            }
+        }
+    }
+}
+
+// A Group is written as the number of its members, followed by the members
+#[derive(Serialize, Deserialize)]
+enum SerializedIdentifier {
+    Group(usize),
+    #[serde(untagged)]
+    Identifier(StructuralIdentifier),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedKey {
+    contents: Vec<SerializedIdentifier>,
+}
+
+impl Serialize for StructureKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut contents = Vec::new();
+        let mut pending = vec![self.contents.iter()];
+        while let Some(members) = pending.last_mut() {
+            match members.next() {
+                None => {
+                    pending.pop();
+                }
+                Some(StructuralIdentifier::Group(group)) => {
+                    contents.push(SerializedIdentifier::Group(group.len()));
+                    pending.push(group.iter());
+                }
+                Some(identifier) => contents.push(SerializedIdentifier::Identifier(identifier.clone())),
+            }
+        }
+        SerializedKey { contents }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StructureKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = SerializedKey::deserialize(deserializer)?;
+        let mut contents = Vec::new();
+        // Groups still missing members, with the number of members they are missing
+        let mut open: Vec<(usize, Vec<StructuralIdentifier>)> = Vec::new();
+        for identifier in serialized.contents {
+            let mut completed = match identifier {
+                SerializedIdentifier::Group(0) => Some(StructuralIdentifier::Group(Vec::new())),
+                SerializedIdentifier::Group(length) => {
+                    open.push((length, Vec::with_capacity(length)));
+                    None
+                }
+                SerializedIdentifier::Identifier(identifier) => Some(identifier),
+            };
+            while let Some(identifier) = completed.take() {
+                let Some((missing, members)) = open.last_mut() else {
+                    contents.push(identifier);
+                    break;
+                };
+                members.push(identifier);
+                *missing -= 1;
+                if *missing == 0 {
+                    let (_, members) = open.pop().expect("a group is open");
+                    completed = Some(StructuralIdentifier::Group(members));
+                }
+            }
+        }
+        if !open.is_empty() {
+            return Err(D::Error::custom("a group has fewer members than its length"));
+        }
+        Ok(StructureKey { contents })
+    }
+}
+
+#[cfg(test)]
+mod serialization_tests {
+    use super::*;
+    use crate::graph::node_frame::UnaryOp;
+    use crate::graph::node_transformer::NodeTransformer;
+    use crate::operations::UnaryOperation;
+    use crate::parser::parse;
+    use crate::simplifier::modules::classical_evaluator::ClassicalEvaluator;
+
+    #[test]
+    fn round_trip_preserves_structure() -> anyhow::Result<()> {
+        let graph = parse("x + 1.5 if not 'a' in s < 0b0101 else -(3) & y")?;
+        let json = serde_json::to_string(&graph)?;
+        let restored: BoxedNode = serde_json::from_str(&json)?;
+        assert_eq!(graph.get_structure_key(), restored.get_structure_key());
+        Ok(())
+    }
+
+    #[test]
+    fn non_finite_doubles_round_trip() -> anyhow::Result<()> {
+        let infinite = ClassicalEvaluator::default().transform_node(parse("1e308 * 10 - x")?).expect("nothing is dropped");
+        let json = serde_json::to_string(&infinite)?;
+        assert!(json.contains(r#"{"Double":"inf"}"#));
+        let restored: BoxedNode = serde_json::from_str(&json)?;
+        assert_eq!(infinite.get_structure_key(), restored.get_structure_key());
+
+        for value in [f64::NAN, f64::NEG_INFINITY, -0.0, 1e-300] {
+            let json = serde_json::to_string(&BoxedNode::double(value))?;
+            let restored: BoxedNode = serde_json::from_str(&json)?;
+            assert_eq!(BoxedNode::double(value).get_structure_key(), restored.get_structure_key());
+        }
+        assert!(serde_json::from_str::<BoxedNode>(r#"{"version": 2, "nodes": [{"NumericConstant": {"Double": "e"}}]}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn structure_keys_are_written_flat() -> anyhow::Result<()> {
+        let key = parse("-(x + 1) < 2.5")?.get_structure_key();
+        let written = serde_json::to_value(&key)?;
+        assert_eq!(Some(&serde_json::json!({"Group": 2})), written["contents"].get(1));
+        let restored: StructureKey = serde_json::from_value(written)?;
+        assert_eq!(key, restored);
+
+        // Deeper than serde_json's recursion limit of 128
+        let depth = 1_000;
+        let mut graph = BoxedNode::boolean(true);
+        for _ in 0..depth {
+            graph = BoxedNode::unary(UnaryOperation::Not, graph);
+        }
+        let key = graph.get_structure_key();
+        let restored: StructureKey = serde_json::from_str(&serde_json::to_string(&key)?)?;
+        assert_eq!(key.fingerprint(), restored.fingerprint());
+
+        let truncated = r#"{"contents": [{"UnaryOp": "Not"}, {"Group": 2}, {"BooleanConstant": true}]}"#;
+        assert!(serde_json::from_str::<StructureKey>(truncated).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn documented_schema() -> anyhow::Result<()> {
+        let json = r#"{
+            "version": 2,
+            "nodes": [
+                {"FunctionParameter": {"identifier": "x"}},
+                {"NumericConstant": {"Int": 1}},
+                {"BinOp": {"operation": "Add", "left": 0, "right": 1}},
+                {"BooleanConstant": true},
+                {"Compare": {"left": 2, "operations": ["LessThan"], "comparators": [3]}}
+            ]
+        }"#;
+        let graph: BoxedNode = serde_json::from_str(json)?;
+        assert_eq!(parse("x + 1 < True")?.get_structure_key(), graph.clone().get_structure_key());
+
+        let written = serde_json::to_value(&graph)?;
+        assert_eq!(Some(2), written["version"].as_u64());
+        assert_eq!(Some(5), written["nodes"].as_array().map(Vec::len));
+        assert_eq!(Some(&serde_json::json!(["LessThan"])), written["nodes"][4]["Compare"].get("operations"));
+        Ok(())
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        for version in [1, 3] {
+            let json = format!(r#"{{"version": {version}, "nodes": [{{"BooleanConstant": true}}]}}"#);
+            let error = serde_json::from_str::<BoxedNode>(&json).expect_err("only version 2 is known");
+            assert!(error.to_string().contains(&format!("unsupported graph schema version {version}")));
+        }
+    }
+
+    #[test]
+    fn malformed_references_are_rejected() {
+        let forward = r#"{"version": 2, "nodes": [{"UnaryOp": {"operation": "Not", "operand": 0}}]}"#;
+        assert!(serde_json::from_str::<BoxedNode>(forward).is_err());
+
+        let shared = r#"{"version": 2, "nodes": [
+            {"BooleanConstant": true},
+            {"BoolOp": {"operator": "And", "operands": [0, 0]}}
+        ]}"#;
+        assert!(serde_json::from_str::<BoxedNode>(shared).is_err());
+
+        let disconnected = r#"{"version": 2, "nodes": [{"BooleanConstant": true}, {"BooleanConstant": false}]}"#;
+        assert!(serde_json::from_str::<BoxedNode>(disconnected).is_err());
+
+        let empty = r#"{"version": 2, "nodes": []}"#;
+        assert!(serde_json::from_str::<BoxedNode>(empty).is_err());
+    }
+
+    #[test]
+    fn deep_trees_do_not_hit_recursion_limits() -> anyhow::Result<()> {
+        let depth = 10_000;
+        let mut graph = BoxedNode { data: NodeFrame::BooleanConstant(true) };
+        for _ in 0..depth {
+            graph = BoxedNode {
+                data: NodeFrame::UnaryOp(UnaryOp {
+                    operation: UnaryOperation::Not,
+                    operand: Box::new(graph),
+                })
+            };
+        }
+
+        let json = serde_json::to_string(&graph)?;
+        let restored: BoxedNode = serde_json::from_str(&json)?;
+        let restored_depth = (&restored).collapse_frames(|frame: NodeFrame<usize>| {
+            frame.children().into_iter().max().map_or(0, |deepest| deepest + 1)
+        });
+        assert_eq!(depth, restored_depth);
+        Ok(())
+    }
+}
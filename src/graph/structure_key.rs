@@ -1,12 +1,62 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use recursion::{Expandable, MappableFrame, PartiallyApplied};
 use crate::graph::node_frame::{BitVec, FunctionParameter, NodeFrame, Numeric};
 use crate::graph::structure_key::StructuralIdentifier::Group;
 use crate::operations::{BinaryOperation, BooleanOperation, ComparisonOperation, UnaryOperation};
 
-#[derive(Debug, Clone, PartialEq)]
+// A Numeric that can be hashed and totally ordered.
+// Doubles are kept as their bits: every NaN is stored as the same canonical NaN so all NaNs share a key,
+// while 0.0 and -0.0 stay apart because they print and divide differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NumericKey {
+    Double(u64),
+    Int(i32),
+}
+
+impl NumericKey {
+    pub fn to_numeric(self) -> Numeric {
+        match self {
+            NumericKey::Double(bits) => Numeric::Double(f64::from_bits(bits)),
+            NumericKey::Int(i) => Numeric::Int(i),
+        }
+    }
+}
+
+impl From<&Numeric> for NumericKey {
+    fn from(numeric: &Numeric) -> Self {
+        match numeric {
+            Numeric::Double(d) if d.is_nan() => NumericKey::Double(f64::NAN.to_bits()),
+            Numeric::Double(d) => NumericKey::Double(d.to_bits()),
+            Numeric::Int(i) => NumericKey::Int(*i),
+        }
+    }
+}
+
+// Doubles come before ints and are ordered by f64::total_cmp, which agrees with comparing the bits for equality
+impl Ord for NumericKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (NumericKey::Double(a), NumericKey::Double(b)) => f64::from_bits(*a).total_cmp(&f64::from_bits(*b)),
+            (NumericKey::Int(a), NumericKey::Int(b)) => a.cmp(b),
+            (NumericKey::Double(_), NumericKey::Int(_)) => Ordering::Less,
+            (NumericKey::Int(_), NumericKey::Double(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for NumericKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StructuralIdentifier {
     FunctionParameter(FunctionParameter),
-    NumericConstant(Numeric),
+    NumericConstant(NumericKey),
     StringConstant(String),
     BooleanConstant(bool),
     BitVec(BitVec),
@@ -15,58 +65,180 @@ pub enum StructuralIdentifier {
     BoolOp(BooleanOperation),
     Compare(Vec<ComparisonOperation>),
     If,
+    Measure,
+    Encode,
+    // A parameter identified by the order in which parameters are first used, see alpha_equivalence
+    ParameterIndex(usize),
+    // Serialized through StructureKey, which writes groups flat
+    #[cfg_attr(feature = "serde", serde(skip))]
     Group(Vec<StructuralIdentifier>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl StructuralIdentifier {
+    // Identifies a node by everything except its children
+    pub fn of_node<T>(frame: &NodeFrame<T>) -> Self {
+        match frame {
+            NodeFrame::FunctionParameter(p) => StructuralIdentifier::FunctionParameter(p.clone()),
+            NodeFrame::NumericConstant(n) => StructuralIdentifier::NumericConstant(n.into()),
+            NodeFrame::StringConstant(s) => StructuralIdentifier::StringConstant(s.clone()),
+            NodeFrame::BooleanConstant(b) => StructuralIdentifier::BooleanConstant(*b),
+            NodeFrame::BitVec(bv) => StructuralIdentifier::BitVec(bv.clone()),
+            NodeFrame::BinOp(binop) => StructuralIdentifier::BinOp(binop.operation),
+            NodeFrame::UnaryOp(unaryop) => StructuralIdentifier::UnaryOp(unaryop.operation),
+            NodeFrame::BoolOp(boolop) => StructuralIdentifier::BoolOp(boolop.operator),
+            NodeFrame::Compare(compare) => StructuralIdentifier::Compare(compare.operations.clone()),
+            NodeFrame::If(_) => StructuralIdentifier::If,
+            NodeFrame::Measure(_) => StructuralIdentifier::Measure,
+            NodeFrame::Encode(_) => StructuralIdentifier::Encode,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct StructureKey {
     pub contents: Vec<StructuralIdentifier>
 }
 
+impl StructureKey {
+    // 128 bit FNV-1a over the key, for cheap lookups and comparisons of large graphs.
+    // It is stable within a build of the crate, but should not be persisted
+    pub fn fingerprint(&self) -> u128 {
+        let mut hasher = Fnv1a128::default();
+        self.hash(&mut hasher);
+        hasher.state
+    }
+}
+
+struct Fnv1a128 {
+    state: u128,
+}
+
+impl Default for Fnv1a128 {
+    fn default() -> Self {
+        Self {
+            state: 0x6c62272e07bb014262b821756295c58d,
+        }
+    }
+}
+
+// Integers are written little endian so the fingerprint does not depend on the platform
+impl Hasher for Fnv1a128 {
+    fn finish(&self) -> u64 {
+        self.state as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= *byte as u128;
+            self.state = self.state.wrapping_mul(0x0000000001000000000000000000013b);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write(&[i]);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write(&(i as u64).to_le_bytes());
+    }
+}
+
 impl Expandable for StructureKey {
     type FrameToken = NodeFrame<PartiallyApplied>;
 
     fn from_frame(val: <Self::FrameToken as MappableFrame>::Frame<Self>) -> Self {
-        let mut contents: Vec<StructuralIdentifier> = Vec::new();
+        let mut contents: Vec<StructuralIdentifier> = vec![StructuralIdentifier::of_node(&val)];
         match val {
-            NodeFrame::FunctionParameter(p) => {
-                contents.push(StructuralIdentifier::FunctionParameter(p));
-            }
-            NodeFrame::NumericConstant(n) => {
-                contents.push(StructuralIdentifier::NumericConstant(n));
-            }
-            NodeFrame::StringConstant(s) => {
-                contents.push(StructuralIdentifier::StringConstant(s));
-            }
-            NodeFrame::BooleanConstant(b) => {
-                contents.push(StructuralIdentifier::BooleanConstant(b));
-            }
-            NodeFrame::BitVec(a) => {
-                contents.push(StructuralIdentifier::BitVec(a));
-            }
+            NodeFrame::FunctionParameter(_)
+            | NodeFrame::NumericConstant(_)
+            | NodeFrame::StringConstant(_)
+            | NodeFrame::BooleanConstant(_)
+            | NodeFrame::BitVec(_) => {}
             NodeFrame::BinOp(b) => {
-                contents.push(StructuralIdentifier::BinOp(b.operation)); 
                 contents.push(Group(vec![Group(b.left.contents), Group(b.right.contents)]));
             }
             NodeFrame::UnaryOp(c) => {
-                contents.push(StructuralIdentifier::UnaryOp(c.operation));
                 contents.push(Group(c.operand.contents));
             }
             NodeFrame::BoolOp(d) => {
-                contents.push(StructuralIdentifier::BoolOp(d.operator));
                 contents.push(Group(d.operands.into_iter().map(|key| Group(key.contents)).collect()));
             }
             NodeFrame::Compare(cmp) => {
-                contents.push(StructuralIdentifier::Compare(cmp.operations));
                 contents.push(Group(vec![Group(cmp.left.contents), Group(cmp.comparators.into_iter().map(|key| Group(key.contents)).collect())]));
             }
             NodeFrame::If(if_node) => {
-                contents.push(StructuralIdentifier::If);
                 contents.push(Group(vec![Group(if_node.condition.contents), Group(if_node.success.contents), Group(if_node.failure.contents)]));
             }
+            NodeFrame::Measure(measure) => {
+                contents.push(Group(measure.operand.contents));
+            }
+            NodeFrame::Encode(encode) => {
+                contents.push(Group(encode.operand.contents));
+            }
         }
         Self {
             contents,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod structure_key_tests {
+    use std::collections::HashSet;
+    use super::*;
+    use crate::parser::parse;
+
+    fn key(source: &str) -> anyhow::Result<StructureKey> {
+        Ok(parse(source)?.get_structure_key())
+    }
+
+    #[test]
+    fn keys_can_be_hashed() -> anyhow::Result<()> {
+        let keys: HashSet<StructureKey> = ["x + 1", "x + 1", "1 + x", "x + 1.0"].into_iter()
+            .map(key)
+            .collect::<anyhow::Result<_>>()?;
+        assert_eq!(3, keys.len());
+        Ok(())
+    }
+
+    #[test]
+    fn doubles_are_compared_deliberately() -> anyhow::Result<()> {
+        let nan = |sign: f64| StructureKey::from_frame(NodeFrame::<StructureKey>::NumericConstant(Numeric::Double(sign * f64::NAN)));
+        assert_eq!(nan(1.0), nan(-1.0));
+        assert_ne!(key("0.0")?, key("-0.0")?);
+        assert!(key("-0.0")? < key("0.0")?);
+        assert!(key("-1.5")? < key("0.25")?);
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprints_follow_equality() -> anyhow::Result<()> {
+        assert_eq!(key("a < b if c else -d")?.fingerprint(), key("a < b if c else -d")?.fingerprint());
+        assert_ne!(key("a < b")?.fingerprint(), key("b < a")?.fingerprint());
+        assert_ne!(key("a")?.fingerprint(), key("'a'")?.fingerprint());
+        Ok(())
+    }
+
+    #[test]
+    fn numeric_keys_round_trip() {
+        for numeric in [Numeric::Int(-3), Numeric::Double(2.5), Numeric::Double(-0.0)] {
+            assert_eq!(numeric, NumericKey::from(&numeric).to_numeric());
+        }
+    }
+}
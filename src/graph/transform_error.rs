@@ -0,0 +1,74 @@
+use std::fmt::{Display, Formatter};
+use crate::graph::node_path::NodePath;
+use crate::operations::ArithmeticError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransformErrorKind {
+    // The operand types are not valid for the operation, e.g. `-"text"` or `1.5 & 1`
+    TypeMismatch(String),
+    DivisionByZero,
+    // The result does not fit into the node's numeric representation, or a string would be longer than interpreter::MAX_STRING_LENGTH
+    Overflow,
+    // A node has the wrong number of children, e.g. a Compare with more operations than comparators
+    ArityMismatch { expected: usize, found: usize },
+    UnsupportedOperation(String),
+}
+
+impl Display for TransformErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransformErrorKind::TypeMismatch(description) => write!(f, "type mismatch: {description}"),
+            TransformErrorKind::DivisionByZero => write!(f, "division by zero"),
+            TransformErrorKind::Overflow => write!(f, "arithmetic overflow"),
+            TransformErrorKind::ArityMismatch { expected, found } => {
+                write!(f, "arity mismatch: expected {expected} operand(s), found {found}")
+            }
+            TransformErrorKind::UnsupportedOperation(description) => write!(f, "unsupported operation: {description}"),
+        }
+    }
+}
+
+impl From<ArithmeticError> for TransformErrorKind {
+    fn from(error: ArithmeticError) -> Self {
+        match error {
+            ArithmeticError::DivisionByZero => TransformErrorKind::DivisionByZero,
+            ArithmeticError::Overflow => TransformErrorKind::Overflow,
+            ArithmeticError::NegativeShiftCount => TransformErrorKind::UnsupportedOperation("negative shift count".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformError {
+    pub kind: TransformErrorKind,
+    // Path of the offending node, relative to the node the transformation was started on
+    pub path: NodePath,
+}
+
+impl TransformError {
+    pub fn new(kind: TransformErrorKind) -> Self {
+        Self {
+            kind,
+            path: NodePath::root(),
+        }
+    }
+
+    pub(crate) fn nested_in(mut self, index: usize) -> Self {
+        self.path.prepend(index);
+        self
+    }
+}
+
+impl From<TransformErrorKind> for TransformError {
+    fn from(kind: TransformErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+impl Display for TransformError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.kind, self.path)
+    }
+}
+
+impl std::error::Error for TransformError {}
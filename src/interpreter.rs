@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use recursion::CollapsibleExt;
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{BitVec, NodeFrame, Numeric};
+use crate::graph::node_path::NodePath;
+use crate::graph::transform_error::TransformErrorKind;
+use crate::operations::{BinaryOperation, BooleanOperation, ComparisonOperation, IntegerSemantics, UnaryOperation};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    BitVec(BitVec),
+}
+
+impl Value {
+    // The value of a constant node, None for any other node
+    pub fn from_constant<T>(frame: &NodeFrame<T>) -> Option<Value> {
+        match frame {
+            NodeFrame::NumericConstant(Numeric::Int(i)) => Some(Value::Int(*i)),
+            NodeFrame::NumericConstant(Numeric::Double(d)) => Some(Value::Float(*d)),
+            NodeFrame::BooleanConstant(b) => Some(Value::Bool(*b)),
+            NodeFrame::StringConstant(s) => Some(Value::Str(s.clone())),
+            NodeFrame::BitVec(bv) => Some(Value::BitVec(bv.clone())),
+            _ => None,
+        }
+    }
+
+    // The constant node holding this value
+    pub fn into_node(self) -> BoxedNode {
+        let data = match self {
+            Value::Int(i) => NodeFrame::NumericConstant(Numeric::Int(i)),
+            Value::Float(d) => NodeFrame::NumericConstant(Numeric::Double(d)),
+            Value::Bool(b) => NodeFrame::BooleanConstant(b),
+            Value::Str(s) => NodeFrame::StringConstant(s),
+            Value::BitVec(bv) => NodeFrame::BitVec(bv),
+        };
+        BoxedNode { data }
+    }
+
+    // Python's truth value testing, a bitvec is true when any bit is set like the int it encodes
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Int(i) => *i != 0,
+            Value::Float(d) => *d != 0.0,
+            Value::Bool(b) => *b,
+            Value::Str(s) => !s.is_empty(),
+            Value::BitVec(bv) => bv.bit_string.contains('1'),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Str(_) => "str",
+            Value::BitVec(_) => "bitvec",
+        }
+    }
+
+    // Booleans take part in arithmetic as 0 and 1
+    fn as_numeric(&self) -> Option<Numeric> {
+        match self {
+            Value::Int(i) => Some(Numeric::Int(*i)),
+            Value::Float(d) => Some(Numeric::Double(*d)),
+            Value::Bool(b) => Some(Numeric::Int(*b as i32)),
+            Value::Str(_) | Value::BitVec(_) => None,
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{i}"),
+            Value::Float(d) => write!(f, "{d:?}"),
+            Value::Bool(b) => write!(f, "{}", if *b { "True" } else { "False" }),
+            Value::Str(s) => write!(f, "{s:?}"),
+            Value::BitVec(bv) => write!(f, "0b{}", bv.bit_string),
+        }
+    }
+}
+
+// Values of the FunctionParameters a graph is evaluated with
+#[derive(Debug, Clone, Default)]
+pub struct Env {
+    values: HashMap<String, Value>,
+}
+
+impl Env {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<String>, value: Value) -> Self {
+        self.bind(name, value);
+        self
+    }
+
+    // Replaces an earlier value of the same parameter
+    pub fn bind(&mut self, name: impl Into<String>, value: Value) {
+        self.values.insert(name.into(), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+}
+
+impl<S: Into<String>> FromIterator<(S, Value)> for Env {
+    fn from_iter<I: IntoIterator<Item = (S, Value)>>(iter: I) -> Self {
+        Self {
+            values: iter.into_iter().map(|(name, value)| (name.into(), value)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalErrorKind {
+    UnboundParameter(String),
+    // An operation failed the same way it does when the ClassicalEvaluator folds it
+    Operation(TransformErrorKind),
+}
+
+impl Display for EvalErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalErrorKind::UnboundParameter(name) => write!(f, "parameter `{name}` has no value"),
+            EvalErrorKind::Operation(kind) => write!(f, "{kind}"),
+        }
+    }
+}
+
+impl From<TransformErrorKind> for EvalErrorKind {
+    fn from(kind: TransformErrorKind) -> Self {
+        EvalErrorKind::Operation(kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    pub kind: EvalErrorKind,
+    // Path of the node that failed, relative to the evaluated node
+    pub path: NodePath,
+}
+
+impl EvalError {
+    fn nested_in(mut self, index: usize) -> Self {
+        self.path.prepend(index);
+        self
+    }
+}
+
+impl From<EvalErrorKind> for EvalError {
+    fn from(kind: EvalErrorKind) -> Self {
+        Self {
+            kind,
+            path: NodePath::root(),
+        }
+    }
+}
+
+impl From<TransformErrorKind> for EvalError {
+    fn from(kind: TransformErrorKind) -> Self {
+        EvalErrorKind::from(kind).into()
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.kind, self.path)
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+pub fn evaluate(node: &BoxedNode, env: &Env) -> Result<Value, EvalError> {
+    evaluate_with(node, env, IntegerSemantics::default())
+}
+
+// Computes the value of a graph the way Python would, with ints following the given semantics.
+// Every node is evaluated bottom up, but an error only surfaces when Python would have evaluated the node:
+// untaken If branches, BoolOp operands after the deciding one and comparisons after a false one are ignored.
+// Measure and Encode have no classical effect and evaluate to their operand
+pub fn evaluate_with(node: &BoxedNode, env: &Env, semantics: IntegerSemantics) -> Result<Value, EvalError> {
+    node.collapse_frames(|frame: NodeFrame<Result<Value, EvalError>>| {
+        match frame {
+            NodeFrame::FunctionParameter(p) => env.get(&p.identifier)
+                .cloned()
+                .ok_or_else(|| EvalErrorKind::UnboundParameter(p.identifier).into()),
+            NodeFrame::BinOp(binop) => {
+                let left = child(binop.left, 0)?;
+                let right = child(binop.right, 1)?;
+                Ok(binary_operation(semantics, binop.operation, left, right)?)
+            }
+            NodeFrame::UnaryOp(unaryop) => {
+                let operand = child(unaryop.operand, 0)?;
+                Ok(unary_operation(semantics, unaryop.operation, operand)?)
+            }
+            NodeFrame::BoolOp(boolop) => {
+                // `and` returns the first falsy operand and `or` the first truthy one, or else the last operand
+                let decides = boolop.operator == BooleanOperation::Or;
+                let count = boolop.operands.len();
+                for (index, operand) in boolop.operands.into_iter().enumerate() {
+                    let value = child(operand, index)?;
+                    if value.is_truthy() == decides || index + 1 == count {
+                        return Ok(value);
+                    }
+                }
+                Err(TransformErrorKind::ArityMismatch { expected: 1, found: 0 }.into())
+            }
+            NodeFrame::Compare(compare) => {
+                let (operations, comparators) = (compare.operations, compare.comparators);
+                if operations.len() != comparators.len() {
+                    return Err(TransformErrorKind::ArityMismatch { expected: operations.len(), found: comparators.len() }.into());
+                }
+                if operations.is_empty() {
+                    return Err(TransformErrorKind::ArityMismatch { expected: 1, found: 0 }.into());
+                }
+                // `a < b <= c` is `a < b and b <= c`, stopping at the first pair that is false
+                let mut previous = child(compare.left, 0)?;
+                for (index, (operation, comparator)) in operations.into_iter().zip(comparators).enumerate() {
+                    let comparator = child(comparator, index + 1)?;
+                    if !compare_values(operation, &previous, &comparator)? {
+                        return Ok(Value::Bool(false));
+                    }
+                    previous = comparator;
+                }
+                Ok(Value::Bool(true))
+            }
+            NodeFrame::If(if_node) => {
+                if child(if_node.condition, 0)?.is_truthy() {
+                    child(if_node.success, 1)
+                } else {
+                    child(if_node.failure, 2)
+                }
+            }
+            NodeFrame::Measure(measure) => child(measure.operand, 0),
+            NodeFrame::Encode(encode) => child(encode.operand, 0),
+            constant => Ok(Value::from_constant(&constant).expect("every other node is a constant")),
+        }
+    })
+}
+
+fn child(result: Result<Value, EvalError>, index: usize) -> Result<Value, EvalError> {
+    result.map_err(|error| error.nested_in(index))
+}
+
+fn type_mismatch(description: String) -> TransformErrorKind {
+    TransformErrorKind::TypeMismatch(description)
+}
+
+// The operations below are Python's semantics for the interpreter and for the passes that fold constants
+
+// Longest string in bytes an operation may produce, `'a' * 2147483647` is an Overflow rather than 2 GiB
+pub const MAX_STRING_LENGTH: usize = 1 << 24;
+
+fn check_string_length(length: usize) -> Result<(), TransformErrorKind> {
+    if length > MAX_STRING_LENGTH {
+        return Err(TransformErrorKind::Overflow);
+    }
+    Ok(())
+}
+
+pub(crate) fn binary_operation(semantics: IntegerSemantics, operation: BinaryOperation, left: Value, right: Value) -> Result<Value, TransformErrorKind> {
+    let unsupported = |left: &Value, right: &Value| {
+        type_mismatch(format!("{operation:?} is not defined between {} and {}", left.type_name(), right.type_name()))
+    };
+    match (&left, &right) {
+        // &, | and ^ of two booleans stay booleans
+        (Value::Bool(l), Value::Bool(r)) if matches!(operation, BinaryOperation::BitwiseAnd | BinaryOperation::BitwiseOr | BinaryOperation::BitwiseXor) => {
+            Ok(Value::Bool(bitwise(operation, *l, *r)))
+        }
+        (Value::Str(l), Value::Str(r)) if operation == BinaryOperation::Add => {
+            check_string_length(l.len() + r.len())?;
+            Ok(Value::Str(format!("{l}{r}")))
+        }
+        (Value::Str(s), count) | (count, Value::Str(s)) if operation == BinaryOperation::Multiply => match count.as_numeric() {
+            Some(Numeric::Int(count)) => {
+                let count = count.max(0) as usize;
+                check_string_length(s.len().checked_mul(count).ok_or(TransformErrorKind::Overflow)?)?;
+                Ok(Value::Str(s.repeat(count)))
+            }
+            _ => Err(unsupported(&left, &right)),
+        },
+        (Value::BitVec(l), Value::BitVec(r)) if matches!(operation, BinaryOperation::BitwiseAnd | BinaryOperation::BitwiseOr | BinaryOperation::BitwiseXor) => {
+            if l.length != r.length {
+                return Err(type_mismatch(format!("{operation:?} of bitvecs of length {} and {}", l.length, r.length)));
+            }
+            let bit_string = l.bit_string.chars()
+                .zip(r.bit_string.chars())
+                .map(|(l, r)| if bitwise(operation, l == '1', r == '1') { '1' } else { '0' })
+                .collect();
+            Ok(Value::BitVec(BitVec { length: l.length, bit_string }))
+        }
+        _ => match (left.as_numeric(), right.as_numeric()) {
+            (Some(Numeric::Int(l)), Some(Numeric::Int(r))) => Ok(Value::Int(operation.perform_checked(l, r, semantics)?)),
+            // Mixing an int with a float promotes the int
+            (Some(l), Some(r)) => {
+                let (l, r) = (as_float(&l), as_float(&r));
+                if operation == BinaryOperation::Divide && r == 0.0 {
+                    return Err(TransformErrorKind::DivisionByZero);
+                }
+                operation.perform_arithmetic(l, r)
+                    .map(Value::Float)
+                    .ok_or_else(|| unsupported(&left, &right))
+            }
+            _ => Err(unsupported(&left, &right)),
+        },
+    }
+}
+
+// One of &, | and ^ on single bits
+fn bitwise(operation: BinaryOperation, left: bool, right: bool) -> bool {
+    match operation {
+        BinaryOperation::BitwiseAnd => left & right,
+        BinaryOperation::BitwiseOr => left | right,
+        _ => left ^ right,
+    }
+}
+
+pub(crate) fn unary_operation(semantics: IntegerSemantics, operation: UnaryOperation, operand: Value) -> Result<Value, TransformErrorKind> {
+    Ok(match (operation, operand) {
+        (UnaryOperation::Not, operand) => Value::Bool(!operand.is_truthy()),
+        (UnaryOperation::Invert, Value::Int(i)) => Value::Int(!i),
+        (UnaryOperation::Invert, Value::Bool(b)) => Value::Int(!(b as i32)),
+        (UnaryOperation::Invert, Value::BitVec(bv)) => Value::BitVec(BitVec {
+            length: bv.length,
+            bit_string: bv.bit_string.chars().map(|bit| if bit == '1' { '0' } else { '1' }).collect(),
+        }),
+        (UnaryOperation::UnaryMinus, Value::Int(i)) => Value::Int(semantics.negate(i)?),
+        (UnaryOperation::UnaryMinus, Value::Bool(b)) => Value::Int(-(b as i32)),
+        (UnaryOperation::UnaryMinus, Value::Float(d)) => Value::Float(-d),
+        (operation, operand) => {
+            return Err(type_mismatch(format!("{operation:?} is not defined for {}", operand.type_name())));
+        }
+    })
+}
+
+// Values have no identity of their own, so `is` holds between values of the same type that are equal
+pub(crate) fn compare_values(operation: ComparisonOperation, left: &Value, right: &Value) -> Result<bool, TransformErrorKind> {
+    let unsupported = || type_mismatch(format!("{operation:?} is not defined between {} and {}", left.type_name(), right.type_name()));
+    match operation {
+        ComparisonOperation::Is => return Ok(std::mem::discriminant(left) == std::mem::discriminant(right) && values_equal(left, right)),
+        ComparisonOperation::IsNot => return Ok(std::mem::discriminant(left) != std::mem::discriminant(right) || !values_equal(left, right)),
+        ComparisonOperation::Equal => return Ok(values_equal(left, right)),
+        ComparisonOperation::NotEqual => return Ok(!values_equal(left, right)),
+        _ => {}
+    }
+    match (left, right) {
+        (Value::Str(l), Value::Str(r)) => match operation {
+            ComparisonOperation::In => Ok(r.contains(l.as_str())),
+            ComparisonOperation::NotIn => Ok(!r.contains(l.as_str())),
+            _ => operation.perform(l, r).ok_or_else(unsupported),
+        },
+        _ if matches!(operation, ComparisonOperation::In | ComparisonOperation::NotIn) => Err(unsupported()),
+        _ => match (left.as_numeric(), right.as_numeric()) {
+            (Some(Numeric::Int(l)), Some(Numeric::Int(r))) => operation.perform(&l, &r).ok_or_else(unsupported),
+            (Some(l), Some(r)) => operation.perform(&as_float(&l), &as_float(&r)).ok_or_else(unsupported),
+            _ => Err(unsupported()),
+        },
+    }
+}
+
+// Numbers and booleans compare by value, anything else only equals a value of its own type
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Str(l), Value::Str(r)) => l == r,
+        (Value::BitVec(l), Value::BitVec(r)) => l == r,
+        _ => match (left.as_numeric(), right.as_numeric()) {
+            (Some(Numeric::Int(l)), Some(Numeric::Int(r))) => l == r,
+            (Some(l), Some(r)) => as_float(&l) == as_float(&r),
+            _ => false,
+        },
+    }
+}
+
+fn as_float(numeric: &Numeric) -> f64 {
+    match numeric {
+        Numeric::Double(d) => *d,
+        Numeric::Int(i) => *i as f64,
+    }
+}
+
+#[cfg(test)]
+mod interpreter_tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn eval(source: &str, env: &Env) -> anyhow::Result<Result<Value, EvalError>> {
+        Ok(evaluate(&parse(source)?, env))
+    }
+
+    fn value(source: &str) -> anyhow::Result<Value> {
+        Ok(eval(source, &Env::new())??)
+    }
+
+    fn error(source: &str) -> anyhow::Result<EvalError> {
+        Ok(eval(source, &Env::new())?.expect_err("evaluation fails"))
+    }
+
+    #[test]
+    fn parameters_come_from_the_environment() -> anyhow::Result<()> {
+        let env = Env::new().with("x", Value::Int(3)).with("name", Value::Str("ab".to_string()));
+        assert_eq!(Value::Int(7), eval("x * 2 + 1", &env)??);
+        assert_eq!(Value::Str("abab".to_string()), eval("name * 2", &env)??);
+
+        let unbound = eval("x + y", &env)?.expect_err("y is unbound");
+        assert_eq!(EvalErrorKind::UnboundParameter("y".to_string()), unbound.kind);
+        assert_eq!(NodePath::from_indices(vec![1]), unbound.path);
+        Ok(())
+    }
+
+    #[test]
+    fn arithmetic_follows_python() -> anyhow::Result<()> {
+        assert_eq!(Value::Int(-4), value("-7 / 2")?);
+        assert_eq!(Value::Float(3.5), value("7 / 2.0")?);
+        assert_eq!(Value::Int(2), value("True + 1")?);
+        assert_eq!(Value::Bool(false), value("True & False")?);
+        assert_eq!(Value::Int(-2), value("~True")?);
+        assert_eq!(Value::Str("ab".to_string()), value("'a' + 'b'")?);
+        assert_eq!(Value::Int(12), value("3 << 2 | 0 ^ 0")?);
+        Ok(())
+    }
+
+    #[test]
+    fn arithmetic_errors_are_reported() -> anyhow::Result<()> {
+        assert_eq!(EvalErrorKind::Operation(TransformErrorKind::DivisionByZero), error("1 / 0")?.kind);
+        assert_eq!(EvalErrorKind::Operation(TransformErrorKind::DivisionByZero), error("1.0 / 0")?.kind);
+        assert_eq!(EvalErrorKind::Operation(TransformErrorKind::Overflow), error("2147483647 + 1")?.kind);
+        assert!(matches!(error("1.5 & 1")?.kind, EvalErrorKind::Operation(TransformErrorKind::TypeMismatch(_))));
+        assert!(matches!(error("'a' - 'b'")?.kind, EvalErrorKind::Operation(TransformErrorKind::TypeMismatch(_))));
+        assert!(matches!(error("-'a'")?.kind, EvalErrorKind::Operation(TransformErrorKind::TypeMismatch(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn string_lengths_are_capped() -> anyhow::Result<()> {
+        assert_eq!(EvalErrorKind::Operation(TransformErrorKind::Overflow), error("'ab' * 2147483647")?.kind);
+        assert_eq!(EvalErrorKind::Operation(TransformErrorKind::Overflow), error("'a' * 16777216 + 'b'")?.kind);
+        assert_eq!(Some(MAX_STRING_LENGTH), match value("'a' * 16777216")? {
+            Value::Str(s) => Some(s.len()),
+            _ => None,
+        });
+        assert_eq!(Value::Str(String::new()), value("'ab' * -3")?);
+        Ok(())
+    }
+
+    #[test]
+    fn integer_semantics_are_configurable() -> anyhow::Result<()> {
+        let wrapped = evaluate_with(&parse("2147483647 + 1")?, &Env::new(), IntegerSemantics::Wrapping)?;
+        assert_eq!(Value::Int(i32::MIN), wrapped);
+        Ok(())
+    }
+
+    #[test]
+    fn boolean_operations_return_operands() -> anyhow::Result<()> {
+        assert_eq!(Value::Int(0), value("0 and 1 / 0")?);
+        assert_eq!(Value::Str("b".to_string()), value("'' or 'b'")?);
+        assert_eq!(Value::Float(2.5), value("1 and 2.5")?);
+        assert_eq!(Value::Int(0), value("False or 0")?);
+        assert_eq!(EvalErrorKind::Operation(TransformErrorKind::DivisionByZero), error("1 and 1 / 0")?.kind);
+        Ok(())
+    }
+
+    #[test]
+    fn comparisons_follow_python() -> anyhow::Result<()> {
+        assert_eq!(Value::Bool(true), value("1 < 2.5 <= 3")?);
+        assert_eq!(Value::Bool(true), value("True == 1")?);
+        assert_eq!(Value::Bool(false), value("'1' == 1")?);
+        assert_eq!(Value::Bool(true), value("'bc' in 'abcd'")?);
+        assert_eq!(Value::Bool(true), value("'x' not in 'abcd'")?);
+        assert_eq!(Value::Bool(true), value("1 is 1")?);
+        assert_eq!(Value::Bool(false), value("1 is True")?);
+        assert_eq!(Value::Bool(true), value("1 is not 1.0")?);
+        // The chain stops before the comparison that would fail
+        assert_eq!(Value::Bool(false), value("2 < 1 < 'a'")?);
+        assert!(matches!(error("1 < 'a'")?.kind, EvalErrorKind::Operation(TransformErrorKind::TypeMismatch(_))));
+        assert!(matches!(error("1 in 'a'")?.kind, EvalErrorKind::Operation(TransformErrorKind::TypeMismatch(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_taken_branch_is_evaluated() -> anyhow::Result<()> {
+        assert_eq!(Value::Int(1), value("1 if 'yes' else 1 / 0")?);
+        let failed = error("1 / 0 if True else 1")?;
+        assert_eq!(NodePath::from_indices(vec![1]), failed.path);
+        Ok(())
+    }
+
+    #[test]
+    fn bitvecs_support_bitwise_operations() -> anyhow::Result<()> {
+        let bits = |bit_string: &str| Value::BitVec(BitVec { length: bit_string.len(), bit_string: bit_string.to_string() });
+        let env = Env::from_iter([("a", bits("0110")), ("b", bits("0011")), ("c", bits("01"))]);
+        assert_eq!(bits("0111"), eval("a | b", &env)??);
+        assert_eq!(bits("1101"), eval("~(a & b)", &env)??);
+        assert!(matches!(eval("a & c", &env)?.expect_err("lengths differ").kind, EvalErrorKind::Operation(TransformErrorKind::TypeMismatch(_))));
+        assert_eq!(Value::Bool(false), eval("a == c", &env)??);
+        Ok(())
+    }
+
+    #[test]
+    fn measure_and_encode_are_transparent() -> anyhow::Result<()> {
+        assert_eq!(Value::Int(3), value("measure(encode(1 + 2))")?);
+        Ok(())
+    }
+
+    #[test]
+    fn deep_graphs_do_not_overflow_the_stack() {
+        let depth = 10_000;
+        let mut graph = BoxedNode::parameter("x");
+        for _ in 0..depth {
+            graph = BoxedNode::binary(BinaryOperation::Add, graph, BoxedNode::int(1));
+        }
+        assert_eq!(Ok(Value::Int(depth + 5)), evaluate(&graph, &Env::new().with("x", Value::Int(5))));
+    }
+}
@@ -1,7 +1,9 @@
-mod computing;
+pub mod computing;
 pub mod operations;
 pub mod graph;
-mod simplifier;
+pub mod interpreter;
+pub mod parser;
+pub mod simplifier;
 
 #[cfg(test)]
 mod tests {
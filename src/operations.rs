@@ -1,7 +1,25 @@
 use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Shl, Shr, Sub};
-use std::process::Output;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+// Python operator precedence, from the loosest to the tightest binding
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Precedence {
+    Conditional,
+    Or,
+    And,
+    Not,
+    Comparison,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseAnd,
+    Shift,
+    Additive,
+    Multiplicative,
+    Unary,
+    Atom,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOperation { 
     Add,
     Subtract,
@@ -37,16 +55,153 @@ impl BinaryOperation {
             BinaryOperation::BitwiseXor => left ^ right,
         }
     }
+
+    // Floating point values have no bitwise operations, so those are not performed and yield None
+    pub fn perform_arithmetic<L, R, O>(&self, left: L, right: R) -> Option<O>
+    where L: Add<R, Output = O>
+           + Sub<R, Output = O>
+           + Div<R, Output = O>
+           + Mul<R, Output = O> {
+        match self {
+            BinaryOperation::Add => Some(left + right),
+            BinaryOperation::Subtract => Some(left - right),
+            BinaryOperation::Divide => Some(left / right),
+            BinaryOperation::Multiply => Some(left * right),
+            BinaryOperation::BitwiseAnd
+            | BinaryOperation::BitwiseLeftShift
+            | BinaryOperation::BitwiseRightShift
+            | BinaryOperation::BitwiseOr
+            | BinaryOperation::BitwiseXor => None,
+        }
+    }
+
+    pub fn perform_checked(&self, left: i32, right: i32, semantics: IntegerSemantics) -> Result<i32, ArithmeticError> {
+        if right < 0 && matches!(self, BinaryOperation::BitwiseLeftShift | BinaryOperation::BitwiseRightShift) {
+            return Err(ArithmeticError::NegativeShiftCount);
+        }
+        if right == 0 && *self == BinaryOperation::Divide {
+            return Err(ArithmeticError::DivisionByZero);
+        }
+        match semantics {
+            IntegerSemantics::Python => {
+                // Every i32 operation except a long left shift is exact in i64
+                let (left_wide, right_wide) = (left as i64, right as i64);
+                let exact = match self {
+                    BinaryOperation::Add => left_wide + right_wide,
+                    BinaryOperation::Subtract => left_wide - right_wide,
+                    BinaryOperation::Multiply => left_wide * right_wide,
+                    BinaryOperation::Divide => left_wide.div_euclid(right_wide) - i64::from(right_wide < 0 && left_wide.rem_euclid(right_wide) != 0),
+                    BinaryOperation::BitwiseAnd => left_wide & right_wide,
+                    BinaryOperation::BitwiseOr => left_wide | right_wide,
+                    BinaryOperation::BitwiseXor => left_wide ^ right_wide,
+                    BinaryOperation::BitwiseLeftShift if left == 0 => 0,
+                    BinaryOperation::BitwiseLeftShift if right >= 32 => return Err(ArithmeticError::Overflow),
+                    BinaryOperation::BitwiseLeftShift => left_wide << right_wide,
+                    BinaryOperation::BitwiseRightShift => left_wide >> right_wide.min(63),
+                };
+                i32::try_from(exact).map_err(|_| ArithmeticError::Overflow)
+            }
+            IntegerSemantics::Wrapping => Ok(match self {
+                BinaryOperation::Add => left.wrapping_add(right),
+                BinaryOperation::Subtract => left.wrapping_sub(right),
+                BinaryOperation::Multiply => left.wrapping_mul(right),
+                BinaryOperation::Divide => left.wrapping_div(right),
+                BinaryOperation::BitwiseAnd => left & right,
+                BinaryOperation::BitwiseOr => left | right,
+                BinaryOperation::BitwiseXor => left ^ right,
+                // Bits shifted past the register width are lost instead of wrapping the shift amount around
+                BinaryOperation::BitwiseLeftShift => left.checked_shl(right as u32).unwrap_or(0),
+                BinaryOperation::BitwiseRightShift => left >> right.min(31),
+            }),
+        }
+    }
+
+    pub fn precedence(&self) -> Precedence {
+        match self {
+            BinaryOperation::Add | BinaryOperation::Subtract => Precedence::Additive,
+            BinaryOperation::Divide | BinaryOperation::Multiply => Precedence::Multiplicative,
+            BinaryOperation::BitwiseAnd => Precedence::BitwiseAnd,
+            BinaryOperation::BitwiseLeftShift | BinaryOperation::BitwiseRightShift => Precedence::Shift,
+            BinaryOperation::BitwiseOr => Precedence::BitwiseOr,
+            BinaryOperation::BitwiseXor => Precedence::BitwiseXor,
+        }
+    }
+
+    // The Python source spelling of the operator
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperation::Add => "+",
+            BinaryOperation::Subtract => "-",
+            BinaryOperation::Divide => "/",
+            BinaryOperation::Multiply => "*",
+            BinaryOperation::BitwiseAnd => "&",
+            BinaryOperation::BitwiseLeftShift => "<<",
+            BinaryOperation::BitwiseRightShift => ">>",
+            BinaryOperation::BitwiseOr => "|",
+            BinaryOperation::BitwiseXor => "^",
+        }
+    }
+
+    pub fn is_bitwise(&self) -> bool {
+        !matches!(self, BinaryOperation::Add | BinaryOperation::Subtract | BinaryOperation::Divide | BinaryOperation::Multiply)
+    }
+
+    // Commutative and associative on numbers. Add is neither on strings
+    pub fn is_commutative(&self) -> bool {
+        matches!(self, BinaryOperation::Add | BinaryOperation::Multiply | BinaryOperation::BitwiseAnd | BinaryOperation::BitwiseOr | BinaryOperation::BitwiseXor)
+    }
+}
+
+
+// How integer constants are evaluated
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum IntegerSemantics {
+    // Exact results with floor division, like Python's unbounded ints.
+    // Results that do not fit into Numeric::Int are reported as an overflow rather than truncated
+    #[default]
+    Python,
+    // Two's complement 32 bit registers: results wrap around and division truncates towards zero
+    Wrapping,
 }
 
+impl IntegerSemantics {
+    pub fn negate(&self, operand: i32) -> Result<i32, ArithmeticError> {
+        match self {
+            IntegerSemantics::Python => operand.checked_neg().ok_or(ArithmeticError::Overflow),
+            IntegerSemantics::Wrapping => Ok(operand.wrapping_neg()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArithmeticError {
+    DivisionByZero,
+    Overflow,
+    NegativeShiftCount,
+}
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BooleanOperation {
     And,
     Or
 }
 
 impl BooleanOperation {
+    pub fn precedence(&self) -> Precedence {
+        match self {
+            BooleanOperation::And => Precedence::And,
+            BooleanOperation::Or => Precedence::Or,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BooleanOperation::And => "and",
+            BooleanOperation::Or => "or",
+        }
+    }
+
     pub fn perform(&self, left: bool, right: bool) -> bool
     {
         match self {
@@ -57,7 +212,8 @@ impl BooleanOperation {
 
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOperation {
     Not,
     Invert,
@@ -65,6 +221,21 @@ pub enum UnaryOperation {
 }
 
 impl UnaryOperation {
+    pub fn precedence(&self) -> Precedence {
+        match self {
+            UnaryOperation::Not => Precedence::Not,
+            UnaryOperation::Invert | UnaryOperation::UnaryMinus => Precedence::Unary,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOperation::Not => "not",
+            UnaryOperation::Invert => "~",
+            UnaryOperation::UnaryMinus => "-",
+        }
+    }
+
     pub fn perform<T, R>(&self, operand: T) -> R
     where T: Not<Output = R>
            + Neg<Output = R>{
@@ -76,7 +247,8 @@ impl UnaryOperation {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ComparisonOperation {
     Equal,
     GreaterThan,
@@ -88,4 +260,128 @@ pub enum ComparisonOperation {
     LessThanOrEqual,
     NotEqual,
     NotIn,
-}
\ No newline at end of file
+}
+
+impl ComparisonOperation {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            ComparisonOperation::Equal => "==",
+            ComparisonOperation::GreaterThan => ">",
+            ComparisonOperation::GreaterThanOrEqual => ">=",
+            ComparisonOperation::In => "in",
+            ComparisonOperation::Is => "is",
+            ComparisonOperation::IsNot => "is not",
+            ComparisonOperation::LessThan => "<",
+            ComparisonOperation::LessThanOrEqual => "<=",
+            ComparisonOperation::NotEqual => "!=",
+            ComparisonOperation::NotIn => "not in",
+        }
+    }
+
+    // The operation giving the same result with its operands swapped, `a > b` is `b < a`.
+    // Membership has no mirrored operation
+    pub fn mirrored(&self) -> Option<ComparisonOperation> {
+        match self {
+            ComparisonOperation::GreaterThan => Some(ComparisonOperation::LessThan),
+            ComparisonOperation::GreaterThanOrEqual => Some(ComparisonOperation::LessThanOrEqual),
+            ComparisonOperation::LessThan => Some(ComparisonOperation::GreaterThan),
+            ComparisonOperation::LessThanOrEqual => Some(ComparisonOperation::GreaterThanOrEqual),
+            ComparisonOperation::Equal
+            | ComparisonOperation::NotEqual
+            | ComparisonOperation::Is
+            | ComparisonOperation::IsNot => Some(*self),
+            ComparisonOperation::In | ComparisonOperation::NotIn => None,
+        }
+    }
+
+    // Only the ordering and equality operators can be performed generically,
+    // membership and identity depend on the operand types and yield None here
+    pub fn perform<T: PartialOrd + ?Sized>(&self, left: &T, right: &T) -> Option<bool> {
+        match self {
+            ComparisonOperation::Equal => Some(left == right),
+            ComparisonOperation::GreaterThan => Some(left > right),
+            ComparisonOperation::GreaterThanOrEqual => Some(left >= right),
+            ComparisonOperation::LessThan => Some(left < right),
+            ComparisonOperation::LessThanOrEqual => Some(left <= right),
+            ComparisonOperation::NotEqual => Some(left != right),
+            ComparisonOperation::In
+            | ComparisonOperation::NotIn
+            | ComparisonOperation::Is
+            | ComparisonOperation::IsNot => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod operations_tests {
+    use super::*;
+
+    #[test]
+    fn mirrored_comparisons_agree() {
+        let operations = [
+            ComparisonOperation::Equal, ComparisonOperation::GreaterThan, ComparisonOperation::GreaterThanOrEqual,
+            ComparisonOperation::LessThan, ComparisonOperation::LessThanOrEqual, ComparisonOperation::NotEqual,
+        ];
+        for operation in operations {
+            let mirrored = operation.mirrored().expect("ordering and equality can be mirrored");
+            for (left, right) in [(1, 2), (2, 2), (3, 2)] {
+                assert_eq!(operation.perform(&left, &right), mirrored.perform(&right, &left), "{operation:?} on {left}, {right}");
+            }
+        }
+        assert_eq!(None, ComparisonOperation::In.mirrored());
+    }
+
+    #[test]
+    fn python_division_floors() {
+        let divide = BinaryOperation::Divide;
+        assert_eq!(Ok(3), divide.perform_checked(7, 2, IntegerSemantics::Python));
+        assert_eq!(Ok(-4), divide.perform_checked(-7, 2, IntegerSemantics::Python));
+        assert_eq!(Ok(-4), divide.perform_checked(7, -2, IntegerSemantics::Python));
+        assert_eq!(Ok(3), divide.perform_checked(-7, -2, IntegerSemantics::Python));
+        assert_eq!(Ok(-3), divide.perform_checked(-6, 2, IntegerSemantics::Python));
+    }
+
+    #[test]
+    fn wrapping_division_truncates() {
+        let divide = BinaryOperation::Divide;
+        assert_eq!(Ok(-3), divide.perform_checked(-7, 2, IntegerSemantics::Wrapping));
+        assert_eq!(Ok(i32::MIN), divide.perform_checked(i32::MIN, -1, IntegerSemantics::Wrapping));
+    }
+
+    #[test]
+    fn division_by_zero_is_reported() {
+        for semantics in [IntegerSemantics::Python, IntegerSemantics::Wrapping] {
+            assert_eq!(Err(ArithmeticError::DivisionByZero), BinaryOperation::Divide.perform_checked(1, 0, semantics));
+        }
+    }
+
+    #[test]
+    fn python_overflow_is_reported() {
+        let python = IntegerSemantics::Python;
+        assert_eq!(Err(ArithmeticError::Overflow), BinaryOperation::Divide.perform_checked(i32::MIN, -1, python));
+        assert_eq!(Err(ArithmeticError::Overflow), BinaryOperation::Add.perform_checked(i32::MAX, 1, python));
+        assert_eq!(Err(ArithmeticError::Overflow), BinaryOperation::Multiply.perform_checked(1 << 16, 1 << 16, python));
+        assert_eq!(Err(ArithmeticError::Overflow), BinaryOperation::BitwiseLeftShift.perform_checked(1, 32, python));
+        assert_eq!(Err(ArithmeticError::Overflow), python.negate(i32::MIN));
+    }
+
+    #[test]
+    fn wrapping_arithmetic_wraps() {
+        let wrapping = IntegerSemantics::Wrapping;
+        assert_eq!(Ok(i32::MIN), BinaryOperation::Add.perform_checked(i32::MAX, 1, wrapping));
+        assert_eq!(Ok(0), BinaryOperation::Multiply.perform_checked(1 << 16, 1 << 16, wrapping));
+        assert_eq!(Ok(i32::MIN), wrapping.negate(i32::MIN));
+    }
+
+    #[test]
+    fn long_shifts() {
+        for semantics in [IntegerSemantics::Python, IntegerSemantics::Wrapping] {
+            assert_eq!(Ok(0), BinaryOperation::BitwiseRightShift.perform_checked(5, 40, semantics));
+            assert_eq!(Ok(-1), BinaryOperation::BitwiseRightShift.perform_checked(-5, 40, semantics));
+            assert_eq!(Ok(i32::MIN), BinaryOperation::BitwiseLeftShift.perform_checked(-1, 31, semantics));
+            assert_eq!(Err(ArithmeticError::NegativeShiftCount), BinaryOperation::BitwiseLeftShift.perform_checked(1, -1, semantics));
+        }
+        assert_eq!(Ok(0), BinaryOperation::BitwiseLeftShift.perform_checked(1, 32, IntegerSemantics::Wrapping));
+        assert_eq!(Ok(0), BinaryOperation::BitwiseLeftShift.perform_checked(0, 100, IntegerSemantics::Python));
+    }
+}
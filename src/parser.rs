@@ -0,0 +1,706 @@
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{BinOp, BitVec, BoolOp, Compare, Encode, FunctionParameter, If, Measure, NodeFrame, Numeric, UnaryOp};
+use crate::operations::{BinaryOperation, BooleanOperation, ComparisonOperation, Precedence, UnaryOperation};
+
+// Parentheses and prefix operators recurse, so very deep nesting is rejected instead of exhausting the stack
+const MAX_NESTING_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    // Byte offsets into the source text
+    pub span: Range<usize>,
+}
+
+impl ParseError {
+    fn new(message: impl Into<String>, span: Range<usize>) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}..{}", self.message, self.span.start, self.span.end)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// Parses a single Python expression.
+// Identifiers become FunctionParameters and binary literals such as `0b0101` become BitVecs,
+// keeping their leading zeros as part of the length
+pub fn parse(source: &str) -> Result<BoxedNode, ParseError> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        depth: 0,
+    };
+    let node = parser.parse_expression()?;
+    let trailing = parser.peek();
+    if trailing.kind != TokenKind::EndOfInput {
+        return Err(ParseError::new(format!("unexpected {}", trailing.kind), trailing.span.clone()));
+    }
+    Ok(node)
+}
+
+impl FromStr for BoxedNode {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Integer(u64),
+    Double(f64),
+    BinaryLiteral(String),
+    String(String),
+    Identifier(String),
+    Keyword(&'static str),
+    Operator(&'static str),
+    EndOfInput,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Integer(i) => write!(f, "integer `{i}`"),
+            TokenKind::Double(d) => write!(f, "float `{d}`"),
+            TokenKind::BinaryLiteral(bits) => write!(f, "binary literal `0b{bits}`"),
+            TokenKind::String(s) => write!(f, "string {s:?}"),
+            TokenKind::Identifier(i) => write!(f, "identifier `{i}`"),
+            TokenKind::Keyword(k) => write!(f, "keyword `{k}`"),
+            TokenKind::Operator(o) => write!(f, "`{o}`"),
+            TokenKind::EndOfInput => write!(f, "end of input"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+const KEYWORDS: [&str; 10] = ["and", "or", "not", "in", "is", "if", "else", "True", "False", "None"];
+
+// Longest operators first so that `<<` is not read as two `<`
+const OPERATORS: [&str; 22] = [
+    "**", "//", "<<", ">>", "<=", ">=", "==", "!=",
+    "+", "-", "*", "/", "%", "~", "&", "|", "^", "<", ">", "(", ")", ",",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let start = position;
+        let current = bytes[position];
+
+        if current.is_ascii_whitespace() {
+            position += 1;
+            continue;
+        }
+
+        if current.is_ascii_digit() || (current == b'.' && bytes.get(position + 1).is_some_and(u8::is_ascii_digit)) {
+            let (kind, end) = lex_number(source, start)?;
+            tokens.push(Token { kind, span: start..end });
+            position = end;
+            continue;
+        }
+
+        if current == b'\'' || current == b'"' {
+            let (value, end) = lex_string(source, start)?;
+            tokens.push(Token { kind: TokenKind::String(value), span: start..end });
+            position = end;
+            continue;
+        }
+
+        if current.is_ascii_alphabetic() || current == b'_' {
+            while position < bytes.len() && (bytes[position].is_ascii_alphanumeric() || bytes[position] == b'_') {
+                position += 1;
+            }
+            let word = &source[start..position];
+            let kind = match KEYWORDS.iter().find(|k| **k == word) {
+                Some(keyword) => TokenKind::Keyword(keyword),
+                None => TokenKind::Identifier(word.to_string()),
+            };
+            tokens.push(Token { kind, span: start..position });
+            continue;
+        }
+
+        match OPERATORS.iter().find(|o| source[start..].starts_with(**o)) {
+            Some(operator) => {
+                position += operator.len();
+                tokens.push(Token { kind: TokenKind::Operator(operator), span: start..position });
+            }
+            None => {
+                let character = source[start..].chars().next().unwrap_or_default();
+                return Err(ParseError::new(format!("unexpected character {character:?}"), start..start + character.len_utf8()));
+            }
+        }
+    }
+
+    tokens.push(Token { kind: TokenKind::EndOfInput, span: source.len()..source.len() });
+    Ok(tokens)
+}
+
+fn lex_number(source: &str, start: usize) -> Result<(TokenKind, usize), ParseError> {
+    let bytes = source.as_bytes();
+    let is_digit_or_separator = |b: u8, radix: u32| (b as char).is_digit(radix) || b == b'_';
+
+    if bytes[start] == b'0' && start + 1 < bytes.len() {
+        let radix = match bytes[start + 1] {
+            b'b' | b'B' => Some(2),
+            b'o' | b'O' => Some(8),
+            b'x' | b'X' => Some(16),
+            _ => None,
+        };
+        if let Some(radix) = radix {
+            let mut end = start + 2;
+            while end < bytes.len() && is_digit_or_separator(bytes[end], radix) {
+                end += 1;
+            }
+            let digits: String = source[start + 2..end].chars().filter(|c| *c != '_').collect();
+            if digits.is_empty() {
+                return Err(ParseError::new("missing digits after the radix prefix", start..end));
+            }
+            if radix == 2 {
+                return Ok((TokenKind::BinaryLiteral(digits), end));
+            }
+            let value = u64::from_str_radix(&digits, radix)
+                .map_err(|_| ParseError::new("integer literal is too large", start..end))?;
+            return Ok((TokenKind::Integer(value), end));
+        }
+    }
+
+    let mut end = start;
+    let mut is_double = false;
+    while end < bytes.len() && is_digit_or_separator(bytes[end], 10) {
+        end += 1;
+    }
+    if end < bytes.len() && bytes[end] == b'.' {
+        is_double = true;
+        end += 1;
+        while end < bytes.len() && is_digit_or_separator(bytes[end], 10) {
+            end += 1;
+        }
+    }
+    if end < bytes.len() && (bytes[end] == b'e' || bytes[end] == b'E') {
+        let mut exponent_end = end + 1;
+        if exponent_end < bytes.len() && (bytes[exponent_end] == b'+' || bytes[exponent_end] == b'-') {
+            exponent_end += 1;
+        }
+        if exponent_end < bytes.len() && bytes[exponent_end].is_ascii_digit() {
+            is_double = true;
+            end = exponent_end;
+            while end < bytes.len() && bytes[end].is_ascii_digit() {
+                end += 1;
+            }
+        }
+    }
+
+    let text: String = source[start..end].chars().filter(|c| *c != '_').collect();
+    let kind = if is_double {
+        TokenKind::Double(text.parse().map_err(|_| ParseError::new("malformed float literal", start..end))?)
+    } else {
+        TokenKind::Integer(text.parse().map_err(|_| ParseError::new("integer literal is too large", start..end))?)
+    };
+    Ok((kind, end))
+}
+
+fn lex_string(source: &str, start: usize) -> Result<(String, usize), ParseError> {
+    let quote = source.as_bytes()[start] as char;
+    let mut value = String::new();
+    let mut characters = source[start + 1..].char_indices();
+
+    while let Some((offset, character)) = characters.next() {
+        match character {
+            c if c == quote => return Ok((value, start + 1 + offset + 1)),
+            '\n' => break,
+            '\\' => match characters.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, '0')) => value.push('\0'),
+                Some((_, escaped @ ('\\' | '\'' | '"'))) => value.push(escaped),
+                // Python keeps unknown escape sequences as they are
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+
+    Err(ParseError::new("unterminated string literal", start..source.len()))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position]
+    }
+
+    fn peek_second(&self) -> &TokenKind {
+        let index = (self.position + 1).min(self.tokens.len() - 1);
+        &self.tokens[index].kind
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.position].clone();
+        if token.kind != TokenKind::EndOfInput {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek().kind, TokenKind::Keyword(k) if k == keyword) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    fn eat_operator(&mut self, operator: &str) -> bool {
+        if matches!(self.peek().kind, TokenKind::Operator(o) if o == operator) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+        let token = self.peek();
+        Err(ParseError::new(format!("expected `{keyword}`, found {}", token.kind), token.span.clone()))
+    }
+
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING_DEPTH {
+            return Err(ParseError::new("expression is nested too deeply", self.peek().span.clone()));
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    // expression := disjunction ['if' disjunction 'else' expression]
+    fn parse_expression(&mut self) -> Result<BoxedNode, ParseError> {
+        self.enter()?;
+        let success = self.parse_boolean_operation(BooleanOperation::Or)?;
+        let node = if self.eat_keyword("if") {
+            let condition = self.parse_boolean_operation(BooleanOperation::Or)?;
+            self.expect_keyword("else")?;
+            let failure = self.parse_expression()?;
+            BoxedNode {
+                data: NodeFrame::If(If {
+                    condition: Box::new(condition),
+                    success: Box::new(success),
+                    failure: Box::new(failure),
+                })
+            }
+        } else {
+            success
+        };
+        self.leave();
+        Ok(node)
+    }
+
+    // Chains of the same operator become a single BoolOp, like Python's own ast
+    fn parse_boolean_operation(&mut self, operator: BooleanOperation) -> Result<BoxedNode, ParseError> {
+        let keyword = match operator {
+            BooleanOperation::Or => "or",
+            BooleanOperation::And => "and",
+        };
+
+        let mut operands = vec![Box::new(self.parse_boolean_operand(operator)?)];
+        while self.eat_keyword(keyword) {
+            operands.push(Box::new(self.parse_boolean_operand(operator)?));
+        }
+
+        if operands.len() == 1 {
+            return Ok(*operands.remove(0));
+        }
+        Ok(BoxedNode {
+            data: NodeFrame::BoolOp(BoolOp {
+                operator,
+                operands,
+            })
+        })
+    }
+
+    fn parse_boolean_operand(&mut self, operator: BooleanOperation) -> Result<BoxedNode, ParseError> {
+        match operator {
+            BooleanOperation::Or => self.parse_boolean_operation(BooleanOperation::And),
+            BooleanOperation::And => self.parse_inversion(),
+        }
+    }
+
+    // inversion := 'not' inversion | comparison
+    fn parse_inversion(&mut self) -> Result<BoxedNode, ParseError> {
+        if !self.eat_keyword("not") {
+            return self.parse_comparison();
+        }
+        self.enter()?;
+        let operand = self.parse_inversion()?;
+        self.leave();
+        Ok(BoxedNode {
+            data: NodeFrame::UnaryOp(UnaryOp {
+                operation: UnaryOperation::Not,
+                operand: Box::new(operand),
+            })
+        })
+    }
+
+    fn parse_comparison(&mut self) -> Result<BoxedNode, ParseError> {
+        let left = self.parse_binary_operation(Precedence::BitwiseOr)?;
+        let mut operations = Vec::new();
+        let mut comparators = Vec::new();
+
+        while let Some(operation) = self.eat_comparison_operator()? {
+            operations.push(operation);
+            comparators.push(Box::new(self.parse_binary_operation(Precedence::BitwiseOr)?));
+        }
+
+        if operations.is_empty() {
+            return Ok(left);
+        }
+        Ok(BoxedNode {
+            data: NodeFrame::Compare(Compare {
+                left: Box::new(left),
+                operations,
+                comparators,
+            })
+        })
+    }
+
+    fn eat_comparison_operator(&mut self) -> Result<Option<ComparisonOperation>, ParseError> {
+        let operation = match &self.peek().kind {
+            TokenKind::Operator("<") => ComparisonOperation::LessThan,
+            TokenKind::Operator("<=") => ComparisonOperation::LessThanOrEqual,
+            TokenKind::Operator(">") => ComparisonOperation::GreaterThan,
+            TokenKind::Operator(">=") => ComparisonOperation::GreaterThanOrEqual,
+            TokenKind::Operator("==") => ComparisonOperation::Equal,
+            TokenKind::Operator("!=") => ComparisonOperation::NotEqual,
+            TokenKind::Keyword("in") => ComparisonOperation::In,
+            TokenKind::Keyword("is") => {
+                self.advance();
+                return Ok(Some(if self.eat_keyword("not") { ComparisonOperation::IsNot } else { ComparisonOperation::Is }));
+            }
+            TokenKind::Keyword("not") if self.peek_second() == &TokenKind::Keyword("in") => {
+                self.advance();
+                self.advance();
+                return Ok(Some(ComparisonOperation::NotIn));
+            }
+            _ => return Ok(None),
+        };
+        self.advance();
+        Ok(Some(operation))
+    }
+
+    fn peek_binary_operator(&self) -> Option<BinaryOperation> {
+        match self.peek().kind {
+            TokenKind::Operator("+") => Some(BinaryOperation::Add),
+            TokenKind::Operator("-") => Some(BinaryOperation::Subtract),
+            TokenKind::Operator("*") => Some(BinaryOperation::Multiply),
+            TokenKind::Operator("/") => Some(BinaryOperation::Divide),
+            TokenKind::Operator("&") => Some(BinaryOperation::BitwiseAnd),
+            TokenKind::Operator("|") => Some(BinaryOperation::BitwiseOr),
+            TokenKind::Operator("^") => Some(BinaryOperation::BitwiseXor),
+            TokenKind::Operator("<<") => Some(BinaryOperation::BitwiseLeftShift),
+            TokenKind::Operator(">>") => Some(BinaryOperation::BitwiseRightShift),
+            _ => None,
+        }
+    }
+
+    // Precedence climbing over the left associative binary operators
+    fn parse_binary_operation(&mut self, minimum: Precedence) -> Result<BoxedNode, ParseError> {
+        let mut left = self.parse_unary()?;
+
+        while let Some(operation) = self.peek_binary_operator() {
+            let precedence = operation.precedence();
+            if precedence < minimum {
+                break;
+            }
+            self.advance();
+            let right = self.parse_binary_operation(next_precedence(precedence))?;
+            left = BoxedNode {
+                data: NodeFrame::BinOp(BinOp {
+                    operation,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                })
+            };
+        }
+
+        if let TokenKind::Operator(operator @ ("**" | "//" | "%")) = self.peek().kind {
+            return Err(ParseError::new(format!("operator `{operator}` is not supported"), self.peek().span.clone()));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<BoxedNode, ParseError> {
+        let operation = match self.peek().kind {
+            TokenKind::Operator("-") => UnaryOperation::UnaryMinus,
+            TokenKind::Operator("~") => UnaryOperation::Invert,
+            TokenKind::Operator("+") => {
+                return Err(ParseError::new("unary `+` is not supported", self.peek().span.clone()));
+            }
+            _ => return self.parse_atom(),
+        };
+        let operator = self.advance();
+
+        // A minus directly in front of a number is part of the literal, which is the only way to write i32::MIN
+        if operation == UnaryOperation::UnaryMinus && matches!(self.peek().kind, TokenKind::Integer(_) | TokenKind::Double(_)) {
+            let literal = self.advance();
+            let span = operator.span.start..literal.span.end;
+            let numeric = match literal.kind {
+                TokenKind::Integer(value) => Numeric::Int(integer_literal(-(value as i128), span)?),
+                TokenKind::Double(value) => Numeric::Double(-value),
+                _ => unreachable!("only numeric literals are negated"),
+            };
+            return Ok(BoxedNode { data: NodeFrame::NumericConstant(numeric) });
+        }
+
+        self.enter()?;
+        let operand = self.parse_unary()?;
+        self.leave();
+        Ok(BoxedNode {
+            data: NodeFrame::UnaryOp(UnaryOp {
+                operation,
+                operand: Box::new(operand),
+            })
+        })
+    }
+
+    fn parse_atom(&mut self) -> Result<BoxedNode, ParseError> {
+        let token = self.advance();
+        let data = match token.kind {
+            TokenKind::Integer(value) => NodeFrame::NumericConstant(Numeric::Int(integer_literal(value as i128, token.span)?)),
+            TokenKind::Double(value) => NodeFrame::NumericConstant(Numeric::Double(value)),
+            TokenKind::BinaryLiteral(bits) => NodeFrame::BitVec(BitVec {
+                length: bits.len(),
+                bit_string: bits,
+            }),
+            TokenKind::String(value) => NodeFrame::StringConstant(value),
+            TokenKind::Keyword("True") => NodeFrame::BooleanConstant(true),
+            TokenKind::Keyword("False") => NodeFrame::BooleanConstant(false),
+            TokenKind::Identifier(identifier) if self.eat_operator("(") => self.parse_call(identifier, token.span)?,
+            TokenKind::Identifier(identifier) => NodeFrame::FunctionParameter(FunctionParameter { identifier }),
+            TokenKind::Operator("(") => {
+                let inner = self.parse_expression()?;
+                if !self.eat_operator(")") {
+                    let found = self.peek();
+                    return Err(ParseError::new(format!("expected `)`, found {}", found.kind), found.span.clone()));
+                }
+                return Ok(inner);
+            }
+            TokenKind::Keyword("None") => {
+                return Err(ParseError::new("`None` is not supported", token.span));
+            }
+            other => {
+                return Err(ParseError::new(format!("expected an expression, found {other}"), token.span));
+            }
+        };
+        Ok(BoxedNode { data })
+    }
+
+    // The domain boundaries are the only calls, `measure` and `encode` are still usable as parameter names
+    fn parse_call(&mut self, function: String, span: Range<usize>) -> Result<NodeFrame<Box<BoxedNode>>, ParseError> {
+        let operand = Box::new(self.parse_expression()?);
+        if !self.eat_operator(")") {
+            let found = self.peek();
+            return Err(ParseError::new(format!("expected `)`, found {}", found.kind), found.span.clone()));
+        }
+        match function.as_str() {
+            "measure" => Ok(NodeFrame::Measure(Measure { operand })),
+            "encode" => Ok(NodeFrame::Encode(Encode { operand })),
+            _ => Err(ParseError::new(format!("unknown function `{function}`, only `measure` and `encode` can be called"), span)),
+        }
+    }
+}
+
+fn integer_literal(value: i128, span: Range<usize>) -> Result<i32, ParseError> {
+    i32::try_from(value).map_err(|_| ParseError::new("integer literal does not fit into 32 bits", span))
+}
+
+// The right operand of a left associative operator has to bind strictly tighter
+fn next_precedence(precedence: Precedence) -> Precedence {
+    match precedence {
+        Precedence::Conditional => Precedence::Or,
+        Precedence::Or => Precedence::And,
+        Precedence::And => Precedence::Not,
+        Precedence::Not => Precedence::Comparison,
+        Precedence::Comparison => Precedence::BitwiseOr,
+        Precedence::BitwiseOr => Precedence::BitwiseXor,
+        Precedence::BitwiseXor => Precedence::BitwiseAnd,
+        Precedence::BitwiseAnd => Precedence::Shift,
+        Precedence::Shift => Precedence::Additive,
+        Precedence::Additive => Precedence::Multiplicative,
+        Precedence::Multiplicative => Precedence::Unary,
+        Precedence::Unary | Precedence::Atom => Precedence::Atom,
+    }
+}
+
+#[cfg(test)]
+mod parser_tests {
+    use super::*;
+
+    fn assert_parses_to(source: &str, expected: BoxedNode) -> anyhow::Result<()> {
+        assert_eq!(expected.get_structure_key(), parse(source)?.get_structure_key(), "while parsing {source:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn arithmetic_precedence() -> anyhow::Result<()> {
+        assert_parses_to("1 + 2 * x", BoxedNode::binary(BinaryOperation::Add, BoxedNode::int(1), BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::int(2), BoxedNode::parameter("x"))))?;
+        assert_parses_to("(1 + 2) * x", BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::binary(BinaryOperation::Add, BoxedNode::int(1), BoxedNode::int(2)), BoxedNode::parameter("x")))?;
+        assert_parses_to("a - b - c", BoxedNode::binary(BinaryOperation::Subtract, BoxedNode::binary(BinaryOperation::Subtract, BoxedNode::parameter("a"), BoxedNode::parameter("b")), BoxedNode::parameter("c")))?;
+        assert_parses_to("a / b * c", BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::binary(BinaryOperation::Divide, BoxedNode::parameter("a"), BoxedNode::parameter("b")), BoxedNode::parameter("c")))
+    }
+
+    #[test]
+    fn bitwise_precedence() -> anyhow::Result<()> {
+        // | binds looser than ^, which binds looser than &, which binds looser than shifts
+        let expected = BoxedNode::binary(BinaryOperation::BitwiseOr,
+            BoxedNode::parameter("a"),
+            BoxedNode::binary(BinaryOperation::BitwiseXor,
+                BoxedNode::parameter("b"),
+                BoxedNode::binary(BinaryOperation::BitwiseAnd,
+                    BoxedNode::parameter("c"),
+                    BoxedNode::binary(BinaryOperation::BitwiseLeftShift, BoxedNode::parameter("d"), BoxedNode::binary(BinaryOperation::Add, BoxedNode::int(1), BoxedNode::int(2))),
+                ),
+            ),
+        );
+        assert_parses_to("a | b ^ c & d << 1 + 2", expected)
+    }
+
+    #[test]
+    fn unary_operators() -> anyhow::Result<()> {
+        assert_parses_to("-x * 2", BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::unary(UnaryOperation::UnaryMinus, BoxedNode::parameter("x")), BoxedNode::int(2)))?;
+        assert_parses_to("~~x", BoxedNode::unary(UnaryOperation::Invert, BoxedNode::unary(UnaryOperation::Invert, BoxedNode::parameter("x"))))?;
+        assert_parses_to("-2147483648", BoxedNode::int(i32::MIN))?;
+        assert_parses_to("-(3)", BoxedNode::unary(UnaryOperation::UnaryMinus, BoxedNode::int(3)))
+    }
+
+    #[test]
+    fn boolean_operators() -> anyhow::Result<()> {
+        let expected = BoxedNode::bool_op(BooleanOperation::Or, vec![
+            BoxedNode::bool_op(BooleanOperation::And, vec![BoxedNode::parameter("a"), BoxedNode::parameter("b"), BoxedNode::parameter("c")]),
+            BoxedNode::unary(UnaryOperation::Not, BoxedNode::parameter("d")),
+        ]);
+        assert_parses_to("a and b and c or not d", expected)?;
+
+        let expected = BoxedNode::unary(UnaryOperation::Not, BoxedNode::compare(BoxedNode::parameter("a"), vec![(ComparisonOperation::Equal, BoxedNode::parameter("b"))]));
+        assert_parses_to("not a == b", expected)
+    }
+
+    #[test]
+    fn chained_comparisons() -> anyhow::Result<()> {
+        let expected = BoxedNode::compare(BoxedNode::parameter("a"), vec![
+            (ComparisonOperation::LessThan, BoxedNode::parameter("b")),
+            (ComparisonOperation::LessThanOrEqual, BoxedNode::parameter("c")),
+            (ComparisonOperation::NotIn, BoxedNode::parameter("d")),
+            (ComparisonOperation::IsNot, BoxedNode::parameter("e")),
+            (ComparisonOperation::In, BoxedNode::parameter("f")),
+            (ComparisonOperation::Is, BoxedNode::parameter("g")),
+            (ComparisonOperation::GreaterThan, BoxedNode::parameter("h")),
+            (ComparisonOperation::GreaterThanOrEqual, BoxedNode::parameter("i")),
+            (ComparisonOperation::NotEqual, BoxedNode::parameter("j")),
+            (ComparisonOperation::Equal, BoxedNode::parameter("k")),
+        ]);
+        assert_parses_to("a < b <= c not in d is not e in f is g > h >= i != j == k", expected)
+    }
+
+    #[test]
+    fn conditional_expressions() -> anyhow::Result<()> {
+        let nested = BoxedNode::if_else(
+            BoxedNode::parameter("c"),
+            BoxedNode::parameter("a"),
+            BoxedNode::if_else(BoxedNode::parameter("d"), BoxedNode::parameter("b"), BoxedNode::int(0)),
+        );
+        assert_parses_to("a if c else b if d else 0", nested)
+    }
+
+    #[test]
+    fn literals() -> anyhow::Result<()> {
+        assert_parses_to("1_000", BoxedNode::int(1000))?;
+        assert_parses_to("0x1f", BoxedNode::int(31))?;
+        assert_parses_to("2.5e1", BoxedNode::double(25.0))?;
+        assert_parses_to("'it\\'s'", BoxedNode::string("it's"))?;
+        assert_parses_to("\"a\\nb\"", BoxedNode::string("a\nb"))?;
+        assert_parses_to("True", BoxedNode::boolean(true))?;
+        assert_parses_to("0b0011", BoxedNode { data: NodeFrame::BitVec(BitVec { length: 4, bit_string: "0011".to_string() }) })
+    }
+
+    #[test]
+    fn domain_boundaries() -> anyhow::Result<()> {
+        let expected = BoxedNode {
+            data: NodeFrame::Measure(Measure {
+                operand: Box::new(BoxedNode::binary(BinaryOperation::BitwiseXor, BoxedNode::parameter("q"), BoxedNode {
+                    data: NodeFrame::Encode(Encode { operand: Box::new(BoxedNode::parameter("measure")) }),
+                })),
+            })
+        };
+        assert_parses_to("measure(q ^ encode(measure))", expected)
+    }
+
+    #[test]
+    fn errors_carry_spans() {
+        let error = parse("1 + ").expect_err("missing operand");
+        assert_eq!(4..4, error.span);
+
+        let error = parse("a // b").expect_err("floor division is not supported");
+        assert_eq!(2..4, error.span);
+
+        let error = parse("'open").expect_err("unterminated string");
+        assert_eq!(0..5, error.span);
+
+        let error = parse("2147483648").expect_err("too large for an i32");
+        assert_eq!(0..10, error.span);
+
+        let error = parse("(a + b").expect_err("missing parenthesis");
+        assert_eq!(6..6, error.span);
+
+        let error = parse("a b").expect_err("trailing tokens");
+        assert_eq!(2..3, error.span);
+
+        let error = parse("a $ b").expect_err("unknown character");
+        assert_eq!(2..3, error.span);
+
+        let error = parse("1 + f(x)").expect_err("unknown function");
+        assert_eq!(4..5, error.span);
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let source = format!("{}x{}", "(".repeat(1000), ")".repeat(1000));
+        let error = parse(&source).expect_err("too deep");
+        assert_eq!("expression is nested too deeply", error.message);
+    }
+}
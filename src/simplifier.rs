@@ -1 +1,276 @@
-mod modules;
\ No newline at end of file
+pub mod modules;
+pub mod specialization;
+
+use std::fmt::{Debug, Display, Formatter};
+use crate::computing::signature::ParameterSignature;
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::canonicalization::Canonicalizer;
+use crate::graph::node_transformer::NodeTransformer;
+use crate::graph::structure_key::StructureKey;
+use crate::simplifier::modules::arithmetic_simplifier::ArithmeticSimplifier;
+use crate::simplifier::modules::boolean_simplifier::BooleanSimplifier;
+use crate::simplifier::modules::classical_evaluator::ClassicalEvaluator;
+use crate::simplifier::modules::if_simplifier::IfSimplifier;
+
+pub const DEFAULT_MAX_ITERATIONS: usize = 16;
+
+// The passes Simplifier::from_pass_names knows, with their default configuration
+// and the parameter types from the signature for the passes that need them
+pub const BUILTIN_PASSES: [&str; 5] = ["canonicalize", "classical_evaluator", "boolean", "arithmetic", "if"];
+
+fn builtin_pass(name: &str, signature: &ParameterSignature) -> Option<Box<dyn NodeTransformer>> {
+    let signature = signature.clone();
+    match name {
+        "canonicalize" => Some(Box::new(Canonicalizer::with_signature(signature))),
+        "classical_evaluator" => Some(Box::new(ClassicalEvaluator::default())),
+        "boolean" => Some(Box::new(BooleanSimplifier::with_signature(signature))),
+        "arithmetic" => Some(Box::new(ArithmeticSimplifier::with_signature(signature))),
+        "if" => Some(Box::new(IfSimplifier::with_signature(signature))),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimplifierError {
+    UnknownPass(String),
+    // The pass returned None for the root, leaving nothing to simplify further
+    NodeDropped { pass: String },
+}
+
+impl Display for SimplifierError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimplifierError::UnknownPass(name) => write!(f, "unknown simplifier pass `{name}`"),
+            SimplifierError::NodeDropped { pass } => write!(f, "pass `{pass}` dropped the whole graph"),
+        }
+    }
+}
+
+impl std::error::Error for SimplifierError {}
+
+// A pass that changed the graph, iterations are counted from 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassChange {
+    pub iteration: usize,
+    pub pass: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimplifyReport {
+    pub node: BoxedNode,
+    pub iterations: usize,
+    pub changes: Vec<PassChange>,
+    // False when the iteration cap was hit while passes were still changing the graph
+    pub reached_fixed_point: bool,
+}
+
+// Runs NodeTransformer passes in the order they were registered.
+// Whether a pass changed the graph is decided by comparing StructureKeys before and after it
+pub struct Simplifier {
+    passes: Vec<(String, Box<dyn NodeTransformer>)>,
+    max_iterations: usize,
+}
+
+impl Default for Simplifier {
+    fn default() -> Self {
+        Self {
+            passes: Vec::new(),
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        }
+    }
+}
+
+impl Debug for Simplifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Simplifier")
+            .field("passes", &self.pass_names())
+            .field("max_iterations", &self.max_iterations)
+            .finish()
+    }
+}
+
+impl Simplifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Builds a pipeline out of BUILTIN_PASSES, e.g. from a configuration file
+    pub fn from_pass_names<S: AsRef<str>>(names: &[S], signature: &ParameterSignature) -> Result<Self, SimplifierError> {
+        let mut simplifier = Self::new();
+        for name in names {
+            let name = name.as_ref();
+            let pass = builtin_pass(name, signature).ok_or_else(|| SimplifierError::UnknownPass(name.to_string()))?;
+            simplifier.passes.push((name.to_string(), pass));
+        }
+        Ok(simplifier)
+    }
+
+    pub fn with_pass(mut self, name: impl Into<String>, pass: impl NodeTransformer + 'static) -> Self {
+        self.register(name, pass);
+        self
+    }
+
+    pub fn register(&mut self, name: impl Into<String>, pass: impl NodeTransformer + 'static) {
+        self.passes.push((name.into(), Box::new(pass)));
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.passes.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    // Runs every pass once
+    pub fn run_once(&mut self, node: BoxedNode) -> Result<SimplifyReport, SimplifierError> {
+        self.run(node, 1)
+    }
+
+    // Repeats the passes until a whole iteration leaves the graph unchanged, or the iteration cap is reached
+    pub fn run_to_fixed_point(&mut self, node: BoxedNode) -> Result<SimplifyReport, SimplifierError> {
+        self.run(node, self.max_iterations)
+    }
+
+    fn run(&mut self, mut node: BoxedNode, max_iterations: usize) -> Result<SimplifyReport, SimplifierError> {
+        let mut key = structure_key(&node);
+        let mut changes = Vec::new();
+        let mut iterations = 0;
+        let mut reached_fixed_point = false;
+
+        while iterations < max_iterations && !reached_fixed_point {
+            iterations += 1;
+            reached_fixed_point = true;
+            for (name, pass) in &mut self.passes {
+                node = pass.transform_node(node).ok_or_else(|| SimplifierError::NodeDropped { pass: name.clone() })?;
+                let new_key = structure_key(&node);
+                if new_key != key {
+                    changes.push(PassChange {
+                        iteration: iterations,
+                        pass: name.clone(),
+                    });
+                    key = new_key;
+                    reached_fixed_point = false;
+                }
+            }
+        }
+
+        Ok(SimplifyReport {
+            node,
+            iterations,
+            changes,
+            reached_fixed_point,
+        })
+    }
+}
+
+fn structure_key(node: &BoxedNode) -> StructureKey {
+    node.clone().get_structure_key()
+}
+
+#[cfg(test)]
+mod simplifier_tests {
+    use super::*;
+    use crate::computing::ComputingDomain;
+    use crate::computing::signature::ParameterType;
+    use crate::graph::node_frame::FunctionParameter;
+    use crate::parser::parse;
+
+    // Strength reduction needs to know x is an int
+    fn int_signature() -> ParameterSignature {
+        ParameterSignature::new().with_parameter("x", ComputingDomain::Classical, ParameterType::Int)
+    }
+
+    fn int_arithmetic() -> ArithmeticSimplifier {
+        ArithmeticSimplifier::with_signature(int_signature())
+    }
+
+    fn change(iteration: usize, pass: &str) -> PassChange {
+        PassChange {
+            iteration,
+            pass: pass.to_string(),
+        }
+    }
+
+    #[test]
+    fn passes_run_in_order() -> anyhow::Result<()> {
+        let mut simplifier = Simplifier::from_pass_names(&["classical_evaluator"], &ParameterSignature::new())?.with_pass("arithmetic", int_arithmetic());
+        let report = simplifier.run_once(parse("x * (2 + 2)")?)?;
+        assert_eq!("x << 2", report.node.to_string());
+        assert_eq!(vec![change(1, "classical_evaluator"), change(1, "arithmetic")], report.changes);
+        Ok(())
+    }
+
+    #[test]
+    fn fixed_point_repeats_until_nothing_changes() -> anyhow::Result<()> {
+        let mut simplifier = Simplifier::new()
+            .with_pass("arithmetic", int_arithmetic())
+            .with_pass("classical_evaluator", ClassicalEvaluator::default());
+        let report = simplifier.run_to_fixed_point(parse("x * (2 + 2)")?)?;
+        assert_eq!("x << 2", report.node.to_string());
+        assert_eq!(3, report.iterations);
+        assert!(report.reached_fixed_point);
+        assert_eq!(vec![change(1, "classical_evaluator"), change(2, "arithmetic")], report.changes);
+        Ok(())
+    }
+
+    #[test]
+    fn iteration_cap_is_reported() -> anyhow::Result<()> {
+        let mut simplifier = Simplifier::from_pass_names(&["arithmetic", "classical_evaluator"], &int_signature())?.with_max_iterations(1);
+        let report = simplifier.run_to_fixed_point(parse("x * (2 + 2)")?)?;
+        assert_eq!("x * 4", report.node.to_string());
+        assert!(!report.reached_fixed_point);
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_passes_use_the_signature() -> anyhow::Result<()> {
+        let mut simplifier = Simplifier::from_pass_names(&["classical_evaluator", "arithmetic"], &ParameterSignature::new())?;
+        let report = simplifier.run_to_fixed_point(parse("x * (2 + 2)")?)?;
+        assert_eq!("x * 4", report.node.to_string());
+
+        let mut simplifier = Simplifier::from_pass_names(&["classical_evaluator", "arithmetic"], &int_signature())?;
+        let report = simplifier.run_to_fixed_point(parse("x * (2 + 2)")?)?;
+        assert_eq!("x << 2", report.node.to_string());
+
+        let signature = ParameterSignature::new().with_parameter("p", ComputingDomain::Classical, ParameterType::Bool);
+        let mut simplifier = Simplifier::from_pass_names(&["boolean"], &signature)?;
+        assert_eq!("p", simplifier.run_once(parse("p and True")?)?.node.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn folded_conditions_remove_branches() -> anyhow::Result<()> {
+        let mut simplifier = Simplifier::from_pass_names(&["classical_evaluator", "if"], &ParameterSignature::new())?;
+        let report = simplifier.run_to_fixed_point(parse("(a if 1 < 2 else b) if c else a")?)?;
+        assert_eq!("a", report.node.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_passes_are_rejected() {
+        let error = Simplifier::from_pass_names(&["classical_evaluator", "magic"], &ParameterSignature::new()).expect_err("magic is not a pass");
+        assert_eq!(SimplifierError::UnknownPass("magic".to_string()), error);
+    }
+
+    struct DropParameters;
+
+    impl NodeTransformer for DropParameters {
+        fn transform_function_parameter(&mut self, _: FunctionParameter) -> Option<BoxedNode> {
+            None
+        }
+    }
+
+    #[test]
+    fn custom_passes_can_be_registered() -> anyhow::Result<()> {
+        let mut simplifier = Simplifier::new()
+            .with_pass("boolean", BooleanSimplifier::default())
+            .with_pass("drop", DropParameters);
+        assert_eq!(vec!["boolean", "drop"], simplifier.pass_names());
+
+        let error = simplifier.run_once(parse("not not x")?).expect_err("x is dropped");
+        assert_eq!(SimplifierError::NodeDropped { pass: "drop".to_string() }, error);
+        Ok(())
+    }
+}
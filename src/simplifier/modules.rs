@@ -1 +1,4 @@
-mod classical_evaluator;
\ No newline at end of file
+pub mod arithmetic_simplifier;
+pub mod boolean_simplifier;
+pub mod classical_evaluator;
+pub mod if_simplifier;
@@ -0,0 +1,290 @@
+use crate::computing::signature::{ParameterSignature, ParameterType};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{BinOp, NodeFrame, Numeric};
+use crate::graph::node_transformer::NodeTransformer;
+use crate::operations::{BinaryOperation, IntegerSemantics};
+
+// Removes arithmetic and bitwise identities and turns multiplication and division by powers of two into shifts.
+// Only BinOps with at least one non constant operand are rewritten, folding constants is the ClassicalEvaluator's job.
+// Every rewrite needs the signature to type the non constant operand, since the identities differ between types:
+// `s * 0` is '' for a string, `True + 0` is the int 1 and `d * 0` is NaN when d is. Operands a rewrite removes
+// must also be droppable, `(1 / a) * 0` still fails when a is 0
+#[derive(Debug, Clone)]
+pub struct ArithmeticSimplifier {
+    pub semantics: IntegerSemantics,
+    pub signature: ParameterSignature,
+    // Cancelling doubles ignores NaN, infinities and signed zeros, when set `d * 0` and `d - d` are left alone
+    pub exact_floating_point: bool,
+}
+
+impl Default for ArithmeticSimplifier {
+    fn default() -> Self {
+        Self {
+            semantics: IntegerSemantics::default(),
+            signature: ParameterSignature::default(),
+            exact_floating_point: true,
+        }
+    }
+}
+
+impl ArithmeticSimplifier {
+    pub fn with_signature(signature: ParameterSignature) -> Self {
+        Self {
+            signature,
+            ..Self::default()
+        }
+    }
+
+    fn type_of(&self, node: &BoxedNode) -> Option<ParameterType> {
+        self.signature.type_of(node).ok().flatten()
+    }
+
+    // The zero an operand of this type cancels to, None when it does not cancel exactly
+    fn zero(&self, operation: BinaryOperation, operand_type: Option<ParameterType>) -> Option<BoxedNode> {
+        match (operation, operand_type?) {
+            // True ^ True is False
+            (BinaryOperation::BitwiseXor, ParameterType::Bool) => Some(BoxedNode::boolean(false)),
+            (_, ParameterType::Int | ParameterType::Bool) => Some(BoxedNode::int(0)),
+            (BinaryOperation::Multiply | BinaryOperation::Subtract, ParameterType::Double) if !self.exact_floating_point => {
+                Some(BoxedNode::double(0.0))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl NodeTransformer for ArithmeticSimplifier {
+    fn transform_binary_operation(&mut self, parameter: BinOp<Option<BoxedNode>>) -> Option<BoxedNode> {
+        let operation = parameter.operation;
+        let (left, right) = (parameter.left?, parameter.right?);
+        let (left_value, right_value) = (int_value(&left), int_value(&right));
+        if left_value.is_some() && right_value.is_some() {
+            return Some(BoxedNode::binary(operation, left, right));
+        }
+
+        let (left_type, right_type) = (self.type_of(&left), self.type_of(&right));
+        let is_int = |t: Option<ParameterType>| matches!(t, Some(ParameterType::Int | ParameterType::Bool));
+        let is_number = |t: Option<ParameterType>| matches!(t, Some(ParameterType::Int | ParameterType::Double));
+        let same_operands = || left.clone().get_structure_key() == right.clone().get_structure_key();
+        let cancelled = match (operation, left_value, right_value) {
+            // x + 0, x - 0, x | 0, x ^ 0, x << 0 and x >> 0 are x for ints, -0.0 + 0 is 0.0 so only x - 0 holds for doubles
+            (BinaryOperation::Add
+            | BinaryOperation::Subtract
+            | BinaryOperation::BitwiseOr
+            | BinaryOperation::BitwiseXor
+            | BinaryOperation::BitwiseLeftShift
+            | BinaryOperation::BitwiseRightShift, _, Some(0))
+                if left_type == Some(ParameterType::Int) || (operation == BinaryOperation::Subtract && left_type == Some(ParameterType::Double)) =>
+            {
+                return Some(left);
+            }
+            (BinaryOperation::Add | BinaryOperation::BitwiseOr | BinaryOperation::BitwiseXor, Some(0), _) if right_type == Some(ParameterType::Int) => return Some(right),
+            // x * 1 and x / 1 are x for ints and doubles, a bool would become an int
+            (BinaryOperation::Multiply | BinaryOperation::Divide, _, Some(1)) if is_number(left_type) => return Some(left),
+            (BinaryOperation::Multiply, Some(1), _) if is_number(right_type) => return Some(right),
+            // x * 0 and x & 0 are 0
+            (BinaryOperation::Multiply | BinaryOperation::BitwiseAnd, _, Some(0)) if left.is_droppable() => self.zero(operation, left_type),
+            (BinaryOperation::Multiply | BinaryOperation::BitwiseAnd, Some(0), _) if right.is_droppable() => self.zero(operation, right_type),
+            (BinaryOperation::Subtract | BinaryOperation::BitwiseXor, None, None) if left.is_droppable() && same_operands() => {
+                self.zero(operation, left_type)
+            }
+            (BinaryOperation::Multiply, _, Some(factor)) if power_of_two(factor).is_some() && is_int(left_type) => {
+                return Some(shift(BinaryOperation::BitwiseLeftShift, left, factor));
+            }
+            (BinaryOperation::Multiply, Some(factor), _) if power_of_two(factor).is_some() && is_int(right_type) => {
+                return Some(shift(BinaryOperation::BitwiseLeftShift, right, factor));
+            }
+            // Only floor division agrees with an arithmetic right shift for negative dividends
+            (BinaryOperation::Divide, _, Some(divisor))
+                if self.semantics == IntegerSemantics::Python && power_of_two(divisor).is_some() && is_int(left_type) =>
+            {
+                return Some(shift(BinaryOperation::BitwiseRightShift, left, divisor));
+            }
+            _ => None,
+        };
+        Some(cancelled.unwrap_or_else(|| BoxedNode::binary(operation, left, right)))
+    }
+}
+
+fn int_value(node: &BoxedNode) -> Option<i32> {
+    match node.data {
+        NodeFrame::NumericConstant(Numeric::Int(value)) => Some(value),
+        _ => None,
+    }
+}
+
+// The exponent of a power of two greater than one
+fn power_of_two(value: i32) -> Option<i32> {
+    (value > 1 && value.count_ones() == 1).then(|| value.trailing_zeros() as i32)
+}
+
+fn shift(operation: BinaryOperation, operand: BoxedNode, factor: i32) -> BoxedNode {
+    let exponent = power_of_two(factor).expect("the factor is a power of two");
+    BoxedNode::binary(operation, operand, BoxedNode::int(exponent))
+}
+
+#[cfg(test)]
+mod arithmetic_simplifier_tests {
+    use super::*;
+    use crate::computing::ComputingDomain;
+    use crate::interpreter::{evaluate, Env, Value};
+    use crate::parser::parse;
+
+    // a and b are ints, p is a bool, d is a double and s a string
+    fn signature() -> ParameterSignature {
+        ParameterSignature::new()
+            .with_parameter("a", ComputingDomain::Classical, ParameterType::Int)
+            .with_parameter("b", ComputingDomain::Classical, ParameterType::Int)
+            .with_parameter("p", ComputingDomain::Classical, ParameterType::Bool)
+            .with_parameter("d", ComputingDomain::Classical, ParameterType::Double)
+            .with_parameter("s", ComputingDomain::Classical, ParameterType::String)
+    }
+
+    fn simplify_with(simplifier: &mut ArithmeticSimplifier, source: &str) -> anyhow::Result<String> {
+        let node = simplifier.transform_node(parse(source)?).expect("nothing is dropped");
+        Ok(node.to_string())
+    }
+
+    fn simplify(source: &str) -> anyhow::Result<String> {
+        simplify_with(&mut ArithmeticSimplifier::with_signature(signature()), source)
+    }
+
+    #[test]
+    fn identities() -> anyhow::Result<()> {
+        assert_eq!("a", simplify("a + 0")?);
+        assert_eq!("a", simplify("0 + a")?);
+        assert_eq!("a", simplify("a - 0")?);
+        assert_eq!("0 - a", simplify("0 - a")?);
+        assert_eq!("a", simplify("1 * a")?);
+        assert_eq!("a", simplify("a / 1")?);
+        assert_eq!("a", simplify("a | 0")?);
+        assert_eq!("a", simplify("0 ^ a")?);
+        assert_eq!("a", simplify("a << 0")?);
+        assert_eq!("a", simplify("a >> 0")?);
+        assert_eq!("d", simplify("d - 0")?);
+        assert_eq!("d", simplify("d * 1")?);
+        Ok(())
+    }
+
+    #[test]
+    fn identities_need_a_known_type() -> anyhow::Result<()> {
+        assert_eq!("x + 0", simplify("x + 0")?);
+        // -0.0 + 0 is 0.0
+        assert_eq!("d + 0", simplify("d + 0")?);
+        // True + 0 and True * 1 are the int 1
+        assert_eq!("p + 0", simplify("p + 0")?);
+        assert_eq!("p * 1", simplify("p * 1")?);
+        // Adding 0 to a string fails
+        assert_eq!("s + 0", simplify("s + 0")?);
+        Ok(())
+    }
+
+    #[test]
+    fn annihilators() -> anyhow::Result<()> {
+        assert_eq!("0", simplify("a * 0")?);
+        assert_eq!("0", simplify("0 & a")?);
+        assert_eq!("0", simplify("p * 0")?);
+        // A negative shift count fails at run time, so the shift is kept
+        assert_eq!("0 >> a - 1", simplify("0 >> (a - 1)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn annihilators_keep_operands_that_fail_or_measure() -> anyhow::Result<()> {
+        assert_eq!("1 / a * 0", simplify("(1 / a) * 0")?);
+        assert_eq!("0 & measure(a)", simplify("0 & measure(a)")?);
+        assert_eq!("0", simplify("(a == b) * 0")?);
+        Ok(())
+    }
+
+    #[test]
+    fn self_cancellation() -> anyhow::Result<()> {
+        assert_eq!("0", simplify("a - a")?);
+        assert_eq!("0", simplify("a ^ a")?);
+        assert_eq!("False", simplify("p ^ p")?);
+        assert_eq!("a - b", simplify("a - b")?);
+        // a + 1 can overflow
+        assert_eq!("a + 1 - (a + 1)", simplify("(a + 1) - (a + 1)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn doubles_and_strings_do_not_cancel() -> anyhow::Result<()> {
+        // NaN - NaN and NaN * 0 are NaN, 'ab' * 0 is ''
+        assert_eq!("d - d", simplify("d - d")?);
+        assert_eq!("d * 0", simplify("d * 0")?);
+        assert_eq!("s * 0", simplify("s * 0")?);
+
+        let mut inexact = ArithmeticSimplifier {
+            exact_floating_point: false,
+            ..ArithmeticSimplifier::with_signature(signature())
+        };
+        assert_eq!("0.0", simplify_with(&mut inexact, "d - d")?);
+        assert_eq!("0.0", simplify_with(&mut inexact, "0 * d")?);
+        assert_eq!("s * 0", simplify_with(&mut inexact, "s * 0")?);
+        Ok(())
+    }
+
+    #[test]
+    fn rewrites_agree_with_the_interpreter() -> anyhow::Result<()> {
+        let env = Env::new()
+            .with("a", Value::Int(3))
+            .with("b", Value::Int(0))
+            .with("p", Value::Bool(true))
+            .with("d", Value::Float(f64::NAN))
+            .with("s", Value::Str("ab".to_string()));
+        let outcome = |node: &BoxedNode| match evaluate(node, &env) {
+            Ok(value) => Ok(value.to_string()),
+            Err(error) => Err(error.kind),
+        };
+        let sources = [
+            "a + 0", "p + 0", "p * 1", "p ^ p", "a * 8", "p * 4", "a / 2", "d - 0", "d * 1",
+            "d - d", "d * 0", "s * 0", "s + 0", "(1 / b) * 0", "a - a",
+        ];
+        for source in sources {
+            let original = parse(source)?;
+            let simplified = ArithmeticSimplifier::with_signature(signature()).transform_node(original.clone()).expect("nothing is dropped");
+            assert_eq!(outcome(&original), outcome(&simplified), "{source} became {simplified}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn strength_reduction() -> anyhow::Result<()> {
+        assert_eq!("a << 3", simplify("a * 8")?);
+        assert_eq!("a + b << 1", simplify("2 * (a + b)")?);
+        assert_eq!("a >> 4", simplify("a / 16")?);
+        assert_eq!("a * 6", simplify("a * 6")?);
+        assert_eq!("a * -2", simplify("a * -2")?);
+        // Truncating division rounds negative dividends the other way than a shift
+        let mut wrapping = ArithmeticSimplifier {
+            semantics: IntegerSemantics::Wrapping,
+            ..ArithmeticSimplifier::with_signature(signature())
+        };
+        assert_eq!("a / 4", simplify_with(&mut wrapping, "a / 4")?);
+        Ok(())
+    }
+
+    #[test]
+    fn strength_reduction_needs_ints() -> anyhow::Result<()> {
+        assert_eq!("s * 2", simplify("s * 2")?);
+        assert_eq!("d * 2", simplify("d * 2")?);
+        assert_eq!("d / 2", simplify("d / 2")?);
+        assert_eq!("x * 2", simplify("x * 2")?);
+        assert_eq!("(a << 1) + d", simplify("a * 2 + d")?);
+        Ok(())
+    }
+
+    #[test]
+    fn rewrites_apply_bottom_up() -> anyhow::Result<()> {
+        assert_eq!("a << 2", simplify("(a + 0) * 4 | 0 * b")?);
+        Ok(())
+    }
+
+    #[test]
+    fn constant_operations_are_left_alone() -> anyhow::Result<()> {
+        assert_eq!("3 + 0", simplify("3 + 0")?);
+        assert_eq!("x + 0.0", simplify("x + 0.0")?);
+        Ok(())
+    }
+}
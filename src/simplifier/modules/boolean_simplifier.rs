@@ -0,0 +1,271 @@
+use crate::computing::signature::{ParameterSignature, ParameterType};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{BoolOp, NodeFrame, UnaryOp};
+use crate::graph::node_transformer::NodeTransformer;
+use crate::graph::structure_key::StructureKey;
+use crate::operations::{BooleanOperation, UnaryOperation};
+
+// Applies the laws of boolean algebra to BoolOps and `not`, also when some operands are not constant.
+// Python's `and` and `or` return one of their operands, so `x and True` is True rather than x when x is 2 and
+// `x or not x` is 2. The laws are only applied to operands the signature proves boolean, comparisons, `not` and
+// Bool parameters, and operands a law removes must be droppable, `measure(q) == 1 and False` still measures.
+// Operands are compared by StructureKey, so canonicalizing first finds more of them
+#[derive(Debug, Clone, Default)]
+pub struct BooleanSimplifier {
+    pub signature: ParameterSignature,
+}
+
+impl BooleanSimplifier {
+    pub fn with_signature(signature: ParameterSignature) -> Self {
+        Self { signature }
+    }
+
+    fn is_bool(&self, node: &BoxedNode) -> bool {
+        matches!(self.signature.type_of(node), Ok(Some(ParameterType::Bool)))
+    }
+
+    // `not` of an already simplified node
+    fn negate(&self, node: BoxedNode) -> BoxedNode {
+        match node.data {
+            NodeFrame::BooleanConstant(value) => BoxedNode::boolean(!value),
+            // Double negation, not not x is x
+            NodeFrame::UnaryOp(UnaryOp { operation: UnaryOperation::Not, operand }) if self.is_bool(&operand) => *operand,
+            // De Morgan, not (not x or not y) is x and y
+            NodeFrame::BoolOp(boolop)
+                if boolop.operands.len() > 1
+                    && boolop.operands.iter().all(|operand| negated_operand(operand).is_some_and(|inner| self.is_bool(inner))) =>
+            {
+                let inner = boolop.operands.into_iter().map(|operand| self.negate(*operand)).collect();
+                BoxedNode::bool_op(dual(boolop.operator), inner)
+            }
+            data => BoxedNode::unary(UnaryOperation::Not, BoxedNode { data }),
+        }
+    }
+
+    // Idempotence, complement and absorption, for operands that are all boolean
+    fn apply_laws(&self, operator: BooleanOperation, operands: Vec<BoxedNode>) -> Result<Vec<BoxedNode>, bool> {
+        let absorbing = operator == BooleanOperation::Or;
+
+        // Idempotence, x and x is x
+        let mut keyed: Vec<(StructureKey, BoxedNode)> = Vec::new();
+        for operand in operands {
+            let key = operand.clone().get_structure_key();
+            if !operand.is_droppable() || keyed.iter().all(|(existing, _)| *existing != key) {
+                keyed.push((key, operand));
+            }
+        }
+
+        // Complement, x and not x is False
+        let complemented = keyed.iter().any(|(_, operand)| {
+            negated_operand(operand).is_some_and(|inner| {
+                let inner_key = inner.clone().get_structure_key();
+                keyed.iter().any(|(key, _)| *key == inner_key)
+            })
+        });
+        if complemented && keyed.iter().all(|(_, operand)| operand.is_droppable()) {
+            return Err(absorbing);
+        }
+
+        // Absorption, x and (x or y) is x
+        let keys: Vec<StructureKey> = keyed.iter().map(|(key, _)| key.clone()).collect();
+        Ok(keyed.into_iter()
+            .filter(|(key, operand)| !(operand.is_droppable() && absorbed(operator, operand, key, &keys)))
+            .map(|(_, operand)| operand)
+            .collect())
+    }
+}
+
+impl NodeTransformer for BooleanSimplifier {
+    fn transform_unary_operation(&mut self, parameter: UnaryOp<Option<BoxedNode>>) -> Option<BoxedNode> {
+        let operand = parameter.operand?;
+        if parameter.operation != UnaryOperation::Not {
+            return Some(BoxedNode::unary(parameter.operation, operand));
+        }
+        Some(self.negate(operand))
+    }
+
+    fn transform_boolean_operation(&mut self, parameter: BoolOp<Vec<Option<BoxedNode>>>) -> Option<BoxedNode> {
+        let operator = parameter.operator;
+        // The constant `x and True` and `x or False` reduce to, and the one `x and False` and `x or True` reduce to
+        let identity = operator == BooleanOperation::And;
+        let absorbing = !identity;
+        let is_constant = |node: &BoxedNode, constant: bool| matches!(node.data, NodeFrame::BooleanConstant(value) if value == constant);
+
+        let mut operands = flatten(operator, parameter.operands.into_iter().flatten().collect());
+        // Operands after an absorbing constant are never evaluated
+        if let Some(position) = operands.iter().position(|operand| is_constant(operand, absorbing)) {
+            operands.truncate(position + 1);
+        }
+        // An identity constant passes evaluation on to the next operand, only the last one can be the result
+        let last = operands.len().saturating_sub(1);
+        let mut operands: Vec<BoxedNode> = operands.into_iter()
+            .enumerate()
+            .filter(|(index, operand)| *index == last || !is_constant(operand, identity))
+            .map(|(_, operand)| operand)
+            .collect();
+        if operands.len() > 1 && is_constant(&operands[operands.len() - 1], identity) && self.is_bool(&operands[operands.len() - 2]) {
+            operands.pop();
+        }
+        // x and False is False only when x is False whenever it is falsy and evaluating it can be skipped
+        if operands.last().is_some_and(|operand| is_constant(operand, absorbing))
+            && operands[..operands.len() - 1].iter().all(|operand| self.is_bool(operand) && operand.is_droppable())
+        {
+            return Some(BoxedNode::boolean(absorbing));
+        }
+
+        if operands.iter().all(|operand| self.is_bool(operand)) {
+            operands = match self.apply_laws(operator, operands) {
+                Ok(operands) => operands,
+                Err(constant) => return Some(BoxedNode::boolean(constant)),
+            };
+        }
+
+        Some(match operands.len() {
+            0 => BoxedNode::boolean(identity),
+            1 => operands.into_iter().next().expect("there is one operand"),
+            // De Morgan, not x and not y is not (x or y), both sides are booleans evaluating the same operands
+            _ if operands.iter().all(|operand| negated_operand(operand).is_some()) => {
+                let inner = operands.into_iter().map(|operand| match operand.data {
+                    NodeFrame::UnaryOp(UnaryOp { operand, .. }) => *operand,
+                    data => BoxedNode { data },
+                }).collect();
+                BoxedNode::unary(UnaryOperation::Not, BoxedNode::bool_op(dual(operator), inner))
+            }
+            _ => BoxedNode::bool_op(operator, operands),
+        })
+    }
+}
+
+fn dual(operator: BooleanOperation) -> BooleanOperation {
+    match operator {
+        BooleanOperation::And => BooleanOperation::Or,
+        BooleanOperation::Or => BooleanOperation::And,
+    }
+}
+
+// Operands of nested BoolOps with the same operator, x and (y and z) is x and y and z
+fn flatten(operator: BooleanOperation, operands: Vec<BoxedNode>) -> Vec<BoxedNode> {
+    let mut flattened = Vec::new();
+    for operand in operands {
+        match operand.data {
+            NodeFrame::BoolOp(nested) if nested.operator == operator => {
+                flattened.extend(nested.operands.into_iter().map(|nested| *nested));
+            }
+            data => flattened.push(BoxedNode { data }),
+        }
+    }
+    flattened
+}
+
+fn absorbed(operator: BooleanOperation, operand: &BoxedNode, key: &StructureKey, keys: &[StructureKey]) -> bool {
+    match &operand.data {
+        NodeFrame::BoolOp(nested) if nested.operator == dual(operator) => nested.operands.iter().any(|nested| {
+            let nested_key = nested.as_ref().clone().get_structure_key();
+            keys.iter().any(|other| other != key && *other == nested_key)
+        }),
+        _ => false,
+    }
+}
+
+fn negated_operand(node: &BoxedNode) -> Option<&BoxedNode> {
+    match &node.data {
+        NodeFrame::UnaryOp(UnaryOp { operation: UnaryOperation::Not, operand }) => Some(operand),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod boolean_simplifier_tests {
+    use super::*;
+    use crate::computing::ComputingDomain;
+    use crate::parser::parse;
+
+    fn simplify(source: &str) -> anyhow::Result<String> {
+        let signature = ParameterSignature::new()
+            .with_parameter("p", ComputingDomain::Classical, ParameterType::Bool)
+            .with_parameter("r", ComputingDomain::Classical, ParameterType::Bool)
+            .with_parameter("a", ComputingDomain::Classical, ParameterType::Int)
+            .with_parameter("q", ComputingDomain::Quantum, ParameterType::BitVec(2));
+        let node = BooleanSimplifier::with_signature(signature).transform_node(parse(source)?).expect("nothing is dropped");
+        Ok(node.to_string())
+    }
+
+    #[test]
+    fn identity_and_annihilation() -> anyhow::Result<()> {
+        assert_eq!("p", simplify("p and True")?);
+        assert_eq!("p or r", simplify("False or p or r or False")?);
+        assert_eq!("False", simplify("p and False and r")?);
+        assert_eq!("True", simplify("p or (r or True)")?);
+        assert_eq!("True", simplify("True and True")?);
+        Ok(())
+    }
+
+    #[test]
+    fn constants_keep_the_value_of_other_operands() -> anyhow::Result<()> {
+        assert_eq!("x and True", simplify("x and True")?);
+        assert_eq!("x", simplify("True and x")?);
+        assert_eq!("a or r", simplify("a or False or r")?);
+        assert_eq!("a or False", simplify("a or False")?);
+        assert_eq!("x and False", simplify("x and False and p")?);
+        Ok(())
+    }
+
+    #[test]
+    fn absorbing_constants_keep_operands_that_fail_or_measure() -> anyhow::Result<()> {
+        assert_eq!("measure(q) == 1 and False", simplify("measure(q) == 1 and False")?);
+        assert_eq!("1 / a < 2 or True", simplify("1 / a < 2 or True or p")?);
+        assert_eq!("False", simplify("not p and a == 1 and False")?);
+        Ok(())
+    }
+
+    #[test]
+    fn idempotence() -> anyhow::Result<()> {
+        assert_eq!("p", simplify("p and p")?);
+        assert_eq!("a == b or p", simplify("a == b or p or (a == b)")?);
+        assert_eq!("a < b or p or a < b", simplify("a < b or p or (a < b)")?);
+        assert_eq!("x and y and x", simplify("x and y and x")?);
+        assert_eq!("measure(q) == 1 and measure(q) == 1", simplify("measure(q) == 1 and measure(q) == 1")?);
+        Ok(())
+    }
+
+    #[test]
+    fn complement() -> anyhow::Result<()> {
+        assert_eq!("True", simplify("p or not p")?);
+        assert_eq!("False", simplify("not (a == b) and r and a == b")?);
+        assert_eq!("x or not x", simplify("x or not x")?);
+        assert_eq!("1 / a < 1 or not 1 / a < 1", simplify("1 / a < 1 or not (1 / a < 1)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn absorption() -> anyhow::Result<()> {
+        assert_eq!("p", simplify("p and (p or r)")?);
+        assert_eq!("p or a == 1", simplify("p or (r and p) or a == 1")?);
+        assert_eq!("x and (x or y)", simplify("x and (x or y)")?);
+        Ok(())
+    }
+
+    #[test]
+    fn negation() -> anyhow::Result<()> {
+        assert_eq!("p", simplify("not not p")?);
+        assert_eq!("not p", simplify("not not not p")?);
+        assert_eq!("False", simplify("not True")?);
+        assert_eq!("not not x", simplify("not not x")?);
+        Ok(())
+    }
+
+    #[test]
+    fn de_morgan() -> anyhow::Result<()> {
+        assert_eq!("not (x or y)", simplify("not x and not y")?);
+        assert_eq!("p and r", simplify("not (not p or not r)")?);
+        assert_eq!("not not (x and y)", simplify("not (not x or not y)")?);
+        assert_eq!("not p or y", simplify("not p or y")?);
+        Ok(())
+    }
+
+    #[test]
+    fn other_nodes_are_untouched() -> anyhow::Result<()> {
+        assert_eq!("-x + 1 if c else ~y", simplify("-x + 1 if c else ~y")?);
+        Ok(())
+    }
+}
@@ -1,115 +1,494 @@
-use std::ops::Not;
-use crate::computing::Computable;
 use crate::graph::boxed_nodes::BoxedNode;
-use crate::graph::node_frame::{BinOp, BoolOp, Compare, NodeFrame, Numeric, UnaryOp};
-use crate::graph::node_transformer::NodeTransformer;
-use crate::operations::{BinaryOperation, BooleanOperation, UnaryOperation};
+use crate::graph::node_frame::{BinOp, BoolOp, Compare, NodeFrame, UnaryOp};
+use crate::graph::node_transformer::{NodeTransformer, TryNodeTransformer};
+use crate::graph::transform_error::{TransformError, TransformErrorKind};
+use crate::interpreter::{binary_operation, compare_values, unary_operation, Value};
+use crate::operations::{BinaryOperation, BooleanOperation, ComparisonOperation, IntegerSemantics, UnaryOperation};
 
-struct ClassicalEvaluator {}
+#[derive(Debug, Clone, Default)]
+pub struct ClassicalEvaluator {
+    pub semantics: IntegerSemantics,
+}
 
+// As a NodeTransformer the evaluator leaves every node it fails to evaluate untouched,
+// so the failure still surfaces when the graph runs. TryNodeTransformer reports those failures instead
 impl NodeTransformer for ClassicalEvaluator {
     fn transform_binary_operation(&mut self, parameter: BinOp<Option<BoxedNode>>) -> Option<BoxedNode> {
         let left = parameter.left?;
         let right = parameter.right?;
-        match (&left.data, &right.data) {
-            (NodeFrame::NumericConstant(Numeric::Int(left_n)), NodeFrame::NumericConstant(Numeric::Int(right_n))) => {
-                let value = parameter.operation.perform(left_n, right_n);
-
-                Some(BoxedNode {
-                    data: NodeFrame::NumericConstant(Numeric::Int(value)),
-                })
-            },
-            (_, _) => {
-                Some(BoxedNode {
-                    data: NodeFrame::BinOp(BinOp {
-                        operation: parameter.operation,
-                        left: Box::new(left),
-                        right: Box::new(right),
-                    })
-                })
-            }
+        match fold_binary_operation(self.semantics, parameter.operation, &left.data, &right.data) {
+            Ok(Some(value)) => Some(value),
+            _ => Some(BoxedNode::binary(parameter.operation, left, right)),
         }
     }
+
     fn transform_unary_operation(&mut self, parameter: UnaryOp<Option<BoxedNode>>) -> Option<BoxedNode> {
         let operand = parameter.operand?;
-        match &operand.data {
-            NodeFrame::NumericConstant(Numeric::Int(n)) => {
-                let value = Numeric::Int(parameter.operation.perform(n));
-                Some( BoxedNode {
-                    data: NodeFrame::NumericConstant(value),
-                })
-            }
-            NodeFrame::BooleanConstant(b) => {
-                assert_ne!(parameter.operation, UnaryOperation::UnaryMinus,
-                           "Unary minus applied to a boolean is non-sensical");
+        match fold_unary_operation(self.semantics, parameter.operation, &operand.data) {
+            Ok(Some(value)) => Some(value),
+            _ => Some(BoxedNode::unary(parameter.operation, operand)),
+        }
+    }
 
-                let value = b.not();
-                Some( BoxedNode {
-                    data: NodeFrame::BooleanConstant(value),
-                })
-            }
+    fn transform_boolean_operation(&mut self, parameter: BoolOp<Vec<Option<BoxedNode>>>) -> Option<BoxedNode> {
+        let filtered_operands: Vec<BoxedNode> = parameter.operands.into_iter().flatten().collect();
+
+        if filtered_operands.is_empty() {
+            return None;
+        }
+        Some(fold_boolean_operation(parameter.operator, filtered_operands))
+    }
+
+    fn transform_comparison(&mut self, parameter: Compare<Option<BoxedNode>, Vec<Option<BoxedNode>>>) -> Option<BoxedNode> {
+        let left = parameter.left?;
+        let filtered_comparators: Vec<Box<BoxedNode>> = parameter.comparators.into_iter().filter_map(|x| Some(Box::new(x?))).collect();
+
+        match fold_comparison(&left, &parameter.operations, &filtered_comparators) {
+            Ok(Some(value)) => Some(BoxedNode::boolean(value)),
+            Err(TransformErrorKind::ArityMismatch { .. }) => None,
             _ => {
-                Some( BoxedNode {
-                    data: NodeFrame::UnaryOp(UnaryOp {
-                        operation: parameter.operation,
-                        operand: Box::new(operand),
+                Some(BoxedNode {
+                    data: NodeFrame::Compare(Compare {
+                        left: Box::new(left),
+                        operations: parameter.operations,
+                        comparators: filtered_comparators,
                     })
                 })
             }
         }
     }
+}
 
-    fn transform_boolean_operation(&mut self, parameter: BoolOp<Vec<Option<BoxedNode>>>) -> Option<BoxedNode> {
+impl TryNodeTransformer for ClassicalEvaluator {
+    fn try_transform_binary_operation(&mut self, parameter: BinOp<BoxedNode>) -> Result<BoxedNode, TransformError> {
+        if let Some(value) = fold_binary_operation(self.semantics, parameter.operation, &parameter.left.data, &parameter.right.data)? {
+            return Ok(value);
+        }
+        Ok(BoxedNode::binary(parameter.operation, parameter.left, parameter.right))
+    }
+
+    fn try_transform_unary_operation(&mut self, parameter: UnaryOp<BoxedNode>) -> Result<BoxedNode, TransformError> {
+        if let Some(value) = fold_unary_operation(self.semantics, parameter.operation, &parameter.operand.data)? {
+            return Ok(value);
+        }
+        Ok(BoxedNode::unary(parameter.operation, parameter.operand))
+    }
 
-        let filtered_operands: Vec<Box<BoxedNode>> = parameter.operands.into_iter().filter_map(|x| Some(Box::new(x?))).collect();
+    fn try_transform_boolean_operation(&mut self, parameter: BoolOp<Vec<BoxedNode>>) -> Result<BoxedNode, TransformError> {
+        if parameter.operands.is_empty() {
+            return Err(TransformErrorKind::ArityMismatch { expected: 1, found: 0 }.into());
+        }
+        Ok(fold_boolean_operation(parameter.operator, parameter.operands))
+    }
 
-        if filtered_operands.is_empty() {
-            return None;
+    fn try_transform_comparison(&mut self, parameter: Compare<BoxedNode, Vec<BoxedNode>>) -> Result<BoxedNode, TransformError> {
+        let comparators: Vec<Box<BoxedNode>> = parameter.comparators.into_iter().map(Box::new).collect();
+        if let Some(value) = fold_comparison(&parameter.left, &parameter.operations, &comparators)? {
+            return Ok(BoxedNode::boolean(value));
+        }
+        Ok(BoxedNode {
+            data: NodeFrame::Compare(Compare {
+                left: Box::new(parameter.left),
+                operations: parameter.operations,
+                comparators,
+            })
+        })
+    }
+}
+
+// Constants are folded with the interpreter's operations, so a folded graph evaluates to what the original would.
+// Ok(None) means the operands are not constants that can be folded
+fn fold_binary_operation(semantics: IntegerSemantics, operation: BinaryOperation, left: &NodeFrame<Box<BoxedNode>>, right: &NodeFrame<Box<BoxedNode>>) -> Result<Option<BoxedNode>, TransformErrorKind> {
+    let (Some(left), Some(right)) = (Value::from_constant(left), Value::from_constant(right)) else {
+        return Ok(None);
+    };
+    Ok(Some(binary_operation(semantics, operation, left, right)?.into_node()))
+}
+
+fn fold_unary_operation(semantics: IntegerSemantics, operation: UnaryOperation, operand: &NodeFrame<Box<BoxedNode>>) -> Result<Option<BoxedNode>, TransformErrorKind> {
+    let Some(operand) = Value::from_constant(operand) else {
+        return Ok(None);
+    };
+    Ok(Some(unary_operation(semantics, operation, operand)?.into_node()))
+}
+
+// Python's `and` returns the first falsy operand and `or` the first truthy one, or else the last operand,
+// so the result is an operand's value rather than a bool: `0 and x` is 0 and `'' or 2.5` is 2.5.
+// Constants that cannot decide the result are dropped unless they are last, `x and True` is True when x is 2.
+// A constant that decides it ends the operation: on its own it is the result, after unknown operands
+// it becomes the last operand and everything after it is never evaluated
+fn fold_boolean_operation(operator: BooleanOperation, operands: Vec<BoxedNode>) -> BoxedNode {
+    let decides = operator == BooleanOperation::Or;
+    let count = operands.len();
+    let mut kept: Vec<BoxedNode> = Vec::new();
+    for (index, operand) in operands.into_iter().enumerate() {
+        match Value::from_constant(&operand.data).map(|value| value.is_truthy()) {
+            Some(truthy) if truthy == decides => {
+                if kept.is_empty() {
+                    return operand;
+                }
+                kept.push(operand);
+                break;
+            }
+            Some(_) if index + 1 < count => {}
+            _ => kept.push(operand),
         }
+    }
+
+    if kept.len() == 1 {
+        return kept.pop().expect("there is one operand");
+    }
+    BoxedNode::bool_op(operator, kept)
+}
 
-        let mut value: bool = match parameter.operator {
-            BooleanOperation::And => true,
-            BooleanOperation::Or => false
+fn fold_comparison(left: &BoxedNode, operations: &[ComparisonOperation], comparators: &[Box<BoxedNode>]) -> Result<Option<bool>, TransformErrorKind> {
+    // There should be at least 1 element in both comparators and operations
+    // Every operation pairs up with exactly one comparator
+    if operations.len() != comparators.len() {
+        return Err(TransformErrorKind::ArityMismatch { expected: operations.len(), found: comparators.len() });
+    }
+    if operations.is_empty() {
+        return Err(TransformErrorKind::ArityMismatch { expected: 1, found: 0 });
+    }
+
+    // Python evaluates `a < b <= c` as `a < b and b <= c`, stopping at the first pair that is false,
+    // so a pair only decides the result when every pair before it was known to hold
+    let mut all_constant = true;
+    let mut previous = Value::from_constant(&left.data);
+    for (operation, comparator) in operations.iter().zip(comparators) {
+        let value = Value::from_constant(&comparator.data);
+        let outcome = match (&previous, &value) {
+            (Some(left), Some(right)) => Some(compare_values(*operation, left, right)),
+            _ => None,
         };
+        match outcome {
+            Some(Ok(true)) => {}
+            Some(Ok(false)) if all_constant => return Ok(Some(false)),
+            Some(Err(kind)) if all_constant => return Err(kind),
+            _ => all_constant = false,
+        }
+        previous = value;
+    }
 
-        for operand in &filtered_operands {
-            // Can only simplify boolean operations when ALL operands are boolean constants
-            if let NodeFrame::BooleanConstant(operand_value) = operand.data {
-                value = parameter.operator.perform(value, operand_value);
-            } else {
-                return Some( BoxedNode {
-                    data: NodeFrame::BoolOp(BoolOp {
-                        operator: parameter.operator,
-                        operands: filtered_operands // Currently returning operands with all None entries removed
-                    })
-                })
+    Ok(all_constant.then_some(true))
+}
+
+#[cfg(test)]
+mod classical_evaluator_tests {
+    use super::*;
+    use crate::graph::node_frame::{BitVec, Numeric};
+    use crate::graph::node_path::NodePath;
+    use crate::interpreter::Env;
+    use crate::parser::parse;
+    use anyhow::Context;
+
+    fn evaluate(node: BoxedNode) -> anyhow::Result<NodeFrame<Box<BoxedNode>>> {
+        let mut evaluator = ClassicalEvaluator::default();
+        Ok(evaluator.transform_node(node).context("comparison should not be dropped")?.data)
+    }
+
+    fn assert_folds_to(node: BoxedNode, expected: bool) -> anyhow::Result<()> {
+        match evaluate(node)? {
+            NodeFrame::BooleanConstant(actual) => assert_eq!(expected, actual),
+            other => panic!("expected a boolean constant, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn equal_and_not_equal() -> anyhow::Result<()> {
+        assert_folds_to(BoxedNode::compare(BoxedNode::int(3), vec![(ComparisonOperation::Equal, BoxedNode::int(3))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::int(3), vec![(ComparisonOperation::Equal, BoxedNode::double(3.0))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::boolean(true), vec![(ComparisonOperation::Equal, BoxedNode::int(1))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::string("a"), vec![(ComparisonOperation::Equal, BoxedNode::int(1))]), false)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::int(3), vec![(ComparisonOperation::NotEqual, BoxedNode::int(4))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::string("a"), vec![(ComparisonOperation::NotEqual, BoxedNode::string("a"))]), false)
+    }
+
+    #[test]
+    fn ordering() -> anyhow::Result<()> {
+        assert_folds_to(BoxedNode::compare(BoxedNode::int(1), vec![(ComparisonOperation::LessThan, BoxedNode::double(1.5))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::int(2), vec![(ComparisonOperation::LessThanOrEqual, BoxedNode::int(2))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::double(2.5), vec![(ComparisonOperation::GreaterThan, BoxedNode::int(3))]), false)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::boolean(false), vec![(ComparisonOperation::GreaterThanOrEqual, BoxedNode::boolean(true))]), false)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::string("abc"), vec![(ComparisonOperation::LessThan, BoxedNode::string("abd"))]), true)
+    }
+
+    #[test]
+    fn membership() -> anyhow::Result<()> {
+        assert_folds_to(BoxedNode::compare(BoxedNode::string("bc"), vec![(ComparisonOperation::In, BoxedNode::string("abcd"))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::string("x"), vec![(ComparisonOperation::In, BoxedNode::string("abcd"))]), false)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::string("x"), vec![(ComparisonOperation::NotIn, BoxedNode::string("abcd"))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::string("a"), vec![(ComparisonOperation::NotIn, BoxedNode::string("abcd"))]), false)
+    }
+
+    #[test]
+    fn identity() -> anyhow::Result<()> {
+        assert_folds_to(BoxedNode::compare(BoxedNode::boolean(true), vec![(ComparisonOperation::Is, BoxedNode::boolean(true))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::boolean(true), vec![(ComparisonOperation::Is, BoxedNode::boolean(false))]), false)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::boolean(false), vec![(ComparisonOperation::IsNot, BoxedNode::boolean(true))]), true)?;
+        assert_folds_to(BoxedNode::compare(BoxedNode::boolean(false), vec![(ComparisonOperation::IsNot, BoxedNode::boolean(false))]), false)
+    }
+
+    #[test]
+    fn chained_comparison_requires_every_pair() -> anyhow::Result<()> {
+        let chain = BoxedNode::compare(BoxedNode::int(1), vec![
+            (ComparisonOperation::LessThan, BoxedNode::int(2)),
+            (ComparisonOperation::LessThanOrEqual, BoxedNode::int(2)),
+            (ComparisonOperation::NotEqual, BoxedNode::int(5)),
+        ]);
+        assert_folds_to(chain, true)?;
+
+        let chain = BoxedNode::compare(BoxedNode::int(1), vec![
+            (ComparisonOperation::LessThan, BoxedNode::int(2)),
+            (ComparisonOperation::GreaterThan, BoxedNode::int(3)),
+        ]);
+        assert_folds_to(chain, false)
+    }
+
+    #[test]
+    fn chained_comparison_short_circuits_before_unknown_operands() -> anyhow::Result<()> {
+        let chain = BoxedNode::compare(BoxedNode::int(3), vec![
+            (ComparisonOperation::LessThan, BoxedNode::int(2)),
+            (ComparisonOperation::LessThan, BoxedNode::parameter("x")),
+        ]);
+        assert_folds_to(chain, false)
+    }
+
+    #[test]
+    fn non_constant_comparison_is_rebuilt() -> anyhow::Result<()> {
+        let chain = BoxedNode::compare(BoxedNode::parameter("x"), vec![
+            (ComparisonOperation::LessThan, BoxedNode::int(2)),
+            (ComparisonOperation::LessThan, BoxedNode::int(1)),
+        ]);
+        match evaluate(chain)? {
+            NodeFrame::Compare(cmp) => {
+                assert_eq!(vec![ComparisonOperation::LessThan, ComparisonOperation::LessThan], cmp.operations);
+                assert_eq!(2, cmp.comparators.len());
             }
+            other => panic!("expected the comparison to be rebuilt, got {other:?}"),
         }
-        Some( BoxedNode {
-            data: NodeFrame::BooleanConstant(value)
-        })
+        Ok(())
     }
 
-    fn transform_comparison(&mut self, parameter: Compare<Option<BoxedNode>, Vec<Option<BoxedNode>>>) -> Option<BoxedNode> {
-        let left = parameter.left?;
-        let filtered_comparators: Vec<Box<BoxedNode>> = parameter.comparators.into_iter().filter_map(|x| Some(Box::new(x?))).collect();
+    #[test]
+    fn invalid_constant_comparison_is_rebuilt() -> anyhow::Result<()> {
+        let ordering = BoxedNode::compare(BoxedNode::string("a"), vec![(ComparisonOperation::LessThan, BoxedNode::int(1))]);
+        assert!(matches!(evaluate(ordering)?, NodeFrame::Compare(_)));
 
-        // There should be at least 1 element in both comparators and operations
-        // The number of operations should also not exceed the number of comparators
-        if parameter.operations.len() > filtered_comparators.len() {
-            return None;
+        let membership = BoxedNode::compare(BoxedNode::int(1), vec![(ComparisonOperation::In, BoxedNode::int(1))]);
+        assert!(matches!(evaluate(membership)?, NodeFrame::Compare(_)));
+        Ok(())
+    }
+
+    fn assert_folds_to_double(node: BoxedNode, expected: f64) -> anyhow::Result<()> {
+        match evaluate(node)? {
+            NodeFrame::NumericConstant(Numeric::Double(actual)) => assert_eq!(expected, actual),
+            other => panic!("expected a double constant, got {other:?}"),
         }
-        if filtered_comparators.is_empty() || parameter.operations.is_empty() {
-            return None;
+        Ok(())
+    }
+
+    #[test]
+    fn double_arithmetic() -> anyhow::Result<()> {
+        assert_folds_to_double(BoxedNode::binary(BinaryOperation::Add, BoxedNode::double(1.5), BoxedNode::double(2.25)), 3.75)?;
+        assert_folds_to_double(BoxedNode::binary(BinaryOperation::Subtract, BoxedNode::double(1.5), BoxedNode::double(2.0)), -0.5)?;
+        assert_folds_to_double(BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::double(1.5), BoxedNode::double(4.0)), 6.0)?;
+        assert_folds_to_double(BoxedNode::binary(BinaryOperation::Divide, BoxedNode::double(1.0), BoxedNode::double(4.0)), 0.25)
+    }
+
+    #[test]
+    fn mixed_arithmetic_promotes_to_double() -> anyhow::Result<()> {
+        assert_folds_to_double(BoxedNode::binary(BinaryOperation::Add, BoxedNode::int(1), BoxedNode::double(0.5)), 1.5)?;
+        assert_folds_to_double(BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::double(0.5), BoxedNode::int(3)), 1.5)?;
+        assert_folds_to_double(BoxedNode::binary(BinaryOperation::Divide, BoxedNode::int(1), BoxedNode::double(2.0)), 0.5)
+    }
+
+    #[test]
+    fn nested_rotation_angle_collapses() -> anyhow::Result<()> {
+        let angle = BoxedNode::binary(BinaryOperation::Divide,
+            BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::double(std::f64::consts::PI), BoxedNode::int(3)),
+            BoxedNode::int(4),
+        );
+        assert_folds_to_double(angle, std::f64::consts::PI * 3.0 / 4.0)
+    }
+
+    #[test]
+    fn bitwise_operations_on_doubles_are_not_folded() -> anyhow::Result<()> {
+        for operation in [
+            BinaryOperation::BitwiseAnd,
+            BinaryOperation::BitwiseOr,
+            BinaryOperation::BitwiseXor,
+            BinaryOperation::BitwiseLeftShift,
+            BinaryOperation::BitwiseRightShift,
+        ] {
+            assert!(matches!(evaluate(BoxedNode::binary(operation, BoxedNode::double(1.0), BoxedNode::int(1)))?, NodeFrame::BinOp(_)));
         }
+        Ok(())
+    }
 
-        let mut expression_result: bool = parameter.operations[0]
+    #[test]
+    fn double_division_by_zero_is_not_folded() -> anyhow::Result<()> {
+        assert!(matches!(evaluate(BoxedNode::binary(BinaryOperation::Divide, BoxedNode::double(1.0), BoxedNode::int(0)))?, NodeFrame::BinOp(_)));
+        Ok(())
+    }
 
+    fn try_evaluate(node: BoxedNode) -> Result<NodeFrame<Box<BoxedNode>>, TransformError> {
+        Ok(ClassicalEvaluator::default().try_transform_node(node)?.data)
+    }
 
+    #[test]
+    fn unary_operations_on_booleans_follow_python() -> anyhow::Result<()> {
+        assert!(matches!(evaluate(BoxedNode::unary(UnaryOperation::Not, BoxedNode::boolean(true)))?, NodeFrame::BooleanConstant(false)));
+        assert!(matches!(evaluate(BoxedNode::unary(UnaryOperation::UnaryMinus, BoxedNode::boolean(true)))?, NodeFrame::NumericConstant(Numeric::Int(-1))));
+        assert!(matches!(evaluate(BoxedNode::unary(UnaryOperation::Invert, BoxedNode::boolean(true)))?, NodeFrame::NumericConstant(Numeric::Int(-2))));
+        assert!(matches!(evaluate(BoxedNode::unary(UnaryOperation::Not, BoxedNode::int(0)))?, NodeFrame::BooleanConstant(true)));
+        Ok(())
+    }
 
+    #[test]
+    fn failing_folds_are_left_in_place() -> anyhow::Result<()> {
+        assert!(matches!(evaluate(BoxedNode::unary(UnaryOperation::UnaryMinus, BoxedNode::string("a")))?, NodeFrame::UnaryOp(_)));
+        assert!(matches!(evaluate(BoxedNode::binary(BinaryOperation::Divide, BoxedNode::int(1), BoxedNode::int(0)))?, NodeFrame::BinOp(_)));
+        Ok(())
+    }
 
+    #[test]
+    fn fallible_evaluation_folds_constants() -> anyhow::Result<()> {
+        let graph = BoxedNode::binary(BinaryOperation::Add, BoxedNode::int(1), BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::int(2), BoxedNode::int(3)));
+        assert!(matches!(try_evaluate(graph)?, NodeFrame::NumericConstant(Numeric::Int(7))));
+        Ok(())
+    }
+
+    #[test]
+    fn fallible_evaluation_reports_division_by_zero_with_path() {
+        let graph = BoxedNode::binary(BinaryOperation::Add, BoxedNode::parameter("x"), BoxedNode::binary(BinaryOperation::Divide, BoxedNode::int(2), BoxedNode::int(0)));
+        let error = try_evaluate(graph).expect_err("division by zero should be reported");
+        assert_eq!(TransformErrorKind::DivisionByZero, error.kind);
+        assert_eq!(NodePath::from_indices(vec![1]), error.path);
+    }
+
+    #[test]
+    fn fallible_evaluation_reports_type_mismatches() {
+        let error = try_evaluate(BoxedNode::unary(UnaryOperation::UnaryMinus, BoxedNode::string("a"))).expect_err("negating a string is invalid");
+        assert!(matches!(error.kind, TransformErrorKind::TypeMismatch(_)));
+
+        let error = try_evaluate(BoxedNode::binary(BinaryOperation::BitwiseAnd, BoxedNode::double(1.0), BoxedNode::int(1))).expect_err("float bitwise is invalid");
+        assert!(matches!(error.kind, TransformErrorKind::TypeMismatch(_)));
+
+        let comparison = BoxedNode::compare(BoxedNode::string("a"), vec![(ComparisonOperation::LessThan, BoxedNode::int(1))]);
+        let error = try_evaluate(comparison).expect_err("ordering strings and ints is invalid");
+        assert!(matches!(error.kind, TransformErrorKind::TypeMismatch(_)));
+    }
+
+    #[test]
+    fn fallible_evaluation_reports_arity_mismatches() {
+        let malformed = BoxedNode {
+            data: NodeFrame::Compare(Compare {
+                left: Box::new(BoxedNode::int(1)),
+                operations: vec![ComparisonOperation::LessThan, ComparisonOperation::LessThan],
+                comparators: vec![Box::new(BoxedNode::int(2))],
+            })
+        };
+        let error = try_evaluate(BoxedNode::unary(UnaryOperation::Not, malformed)).expect_err("the comparison is malformed");
+        assert_eq!(TransformErrorKind::ArityMismatch { expected: 2, found: 1 }, error.kind);
+        assert_eq!(NodePath::from_indices(vec![0]), error.path);
+    }
+
+    #[test]
+    fn fallible_evaluation_reports_overflow() {
+        let error = try_evaluate(BoxedNode::unary(UnaryOperation::UnaryMinus, BoxedNode::int(i32::MIN))).expect_err("-i32::MIN overflows");
+        assert_eq!(TransformErrorKind::Overflow, error.kind);
+    }
 
+    #[test]
+    fn integer_semantics_are_configurable() -> anyhow::Result<()> {
+        let graph = BoxedNode::binary(BinaryOperation::Multiply, BoxedNode::binary(BinaryOperation::Divide, BoxedNode::int(-7), BoxedNode::int(2)), BoxedNode::int(i32::MAX));
 
-        None
+        let mut python = ClassicalEvaluator { semantics: IntegerSemantics::Python };
+        let error = python.try_transform_node(graph.clone()).expect_err("-4 * i32::MAX does not fit into an i32");
+        assert_eq!(TransformErrorKind::Overflow, error.kind);
+
+        let mut wrapping = ClassicalEvaluator { semantics: IntegerSemantics::Wrapping };
+        let folded = wrapping.try_transform_node(graph)?;
+        assert!(matches!(folded.data, NodeFrame::NumericConstant(Numeric::Int(n)) if n == (-3i32).wrapping_mul(i32::MAX)));
+        Ok(())
+    }
+
+    #[test]
+    fn python_floor_division() -> anyhow::Result<()> {
+        assert!(matches!(try_evaluate(BoxedNode::binary(BinaryOperation::Divide, BoxedNode::int(-7), BoxedNode::int(2)))?, NodeFrame::NumericConstant(Numeric::Int(-4))));
+        Ok(())
+    }
+
+    #[test]
+    fn long_shifts_do_not_panic() -> anyhow::Result<()> {
+        assert!(matches!(evaluate(BoxedNode::binary(BinaryOperation::BitwiseLeftShift, BoxedNode::int(1), BoxedNode::int(40)))?, NodeFrame::BinOp(_)));
+        let error = try_evaluate(BoxedNode::binary(BinaryOperation::BitwiseLeftShift, BoxedNode::int(1), BoxedNode::int(-1))).expect_err("negative shifts are invalid");
+        assert!(matches!(error.kind, TransformErrorKind::UnsupportedOperation(_)));
+        Ok(())
+    }
+
+    fn evaluate_to_string(node: BoxedNode) -> anyhow::Result<String> {
+        let mut evaluator = ClassicalEvaluator::default();
+        Ok(evaluator.transform_node(node).context("boolean operation should not be dropped")?.to_string())
+    }
+
+    #[test]
+    fn boolean_operations_short_circuit_on_constants() -> anyhow::Result<()> {
+        let and = |operands| evaluate_to_string(BoxedNode::bool_op(BooleanOperation::And, operands));
+        let or = |operands| evaluate_to_string(BoxedNode::bool_op(BooleanOperation::Or, operands));
+        assert_eq!("False", and(vec![BoxedNode::boolean(false), BoxedNode::parameter("x")])?);
+        assert_eq!("x or True", or(vec![BoxedNode::parameter("x"), BoxedNode::boolean(true)])?);
+        assert_eq!("True", or(vec![BoxedNode::boolean(true), BoxedNode::parameter("x")])?);
+        assert_eq!("x", and(vec![BoxedNode::boolean(true), BoxedNode::parameter("x")])?);
+        assert_eq!("x", or(vec![BoxedNode::boolean(false), BoxedNode::string(""), BoxedNode::parameter("x")])?);
+        assert_eq!("x and y", and(vec![BoxedNode::parameter("x"), BoxedNode::boolean(true), BoxedNode::parameter("y")])?);
+        Ok(())
+    }
+
+    #[test]
+    fn boolean_operations_keep_unknown_operands_before_a_deciding_constant() -> anyhow::Result<()> {
+        assert_eq!("x and 0", evaluate_to_string(BoxedNode::bool_op(BooleanOperation::And, vec![BoxedNode::parameter("x"), BoxedNode::int(0), BoxedNode::parameter("y")]))?);
+        // `x and True` is True rather than x when x is 2
+        assert_eq!("x and True", evaluate_to_string(BoxedNode::bool_op(BooleanOperation::And, vec![BoxedNode::parameter("x"), BoxedNode::boolean(true)]))?);
+        Ok(())
+    }
+
+    #[test]
+    fn boolean_operations_return_operand_values() -> anyhow::Result<()> {
+        assert_eq!("0", evaluate_to_string(BoxedNode::bool_op(BooleanOperation::And, vec![BoxedNode::int(0), BoxedNode::parameter("x")]))?);
+        assert_eq!("2", evaluate_to_string(BoxedNode::bool_op(BooleanOperation::And, vec![BoxedNode::int(1), BoxedNode::int(2)]))?);
+        assert_eq!("2.5", evaluate_to_string(BoxedNode::bool_op(BooleanOperation::Or, vec![BoxedNode::string(""), BoxedNode::double(2.5), BoxedNode::boolean(false)]))?);
+        assert_eq!("''", evaluate_to_string(BoxedNode::bool_op(BooleanOperation::Or, vec![BoxedNode::boolean(false), BoxedNode::string("")]))?);
+        Ok(())
+    }
+
+    #[test]
+    fn fallible_evaluation_short_circuits_boolean_operations() -> anyhow::Result<()> {
+        let folded = try_evaluate(BoxedNode::bool_op(BooleanOperation::Or, vec![BoxedNode::boolean(false), BoxedNode::parameter("x"), BoxedNode::boolean(true), BoxedNode::parameter("y")]))?;
+        assert_eq!("x or True", BoxedNode { data: folded }.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn folding_agrees_with_the_interpreter() -> anyhow::Result<()> {
+        let bits = |bit_string: &str| BoxedNode {
+            data: NodeFrame::BitVec(BitVec { length: bit_string.len(), bit_string: bit_string.to_string() }),
+        };
+        let graphs = [
+            parse("-7 / 2 + True * 2.5")?,
+            parse("'ab' * 2 + 'c'")?,
+            parse("1 is 1 and 1 is not 1.0")?,
+            parse("not (1 < 2.5 <= 3)")?,
+            BoxedNode::bool_op(BooleanOperation::Or, vec![bits("00"), BoxedNode::int(3)]),
+            BoxedNode::unary(UnaryOperation::Invert, BoxedNode::binary(BinaryOperation::BitwiseAnd, bits("0110"), bits("0011"))),
+        ];
+        for graph in graphs {
+            let expected = crate::interpreter::evaluate(&graph, &Env::new())?.into_node();
+            let folded = BoxedNode { data: try_evaluate(graph.clone())? };
+            assert_eq!(expected.get_structure_key(), folded.get_structure_key(), "{graph}");
+        }
+        Ok(())
     }
-}
\ No newline at end of file
+}
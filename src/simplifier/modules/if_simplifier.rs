@@ -0,0 +1,94 @@
+use crate::computing::signature::{ParameterSignature, ParameterType};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{If, NodeFrame};
+use crate::graph::node_transformer::NodeTransformer;
+use crate::operations::UnaryOperation;
+
+// Removes If nodes whose outcome is known: a constant condition selects its branch,
+// and identical branches make the condition irrelevant. The condition is still evaluated first though,
+// so it is only dropped when it is droppable: `x if measure(q) == 1 else x` measures and `x if 1 / a else x` fails.
+// `False if c else True` becomes `not c`, and `True if c else False` becomes `c` when the signature proves c boolean,
+// `True if 2 else False` is True
+#[derive(Debug, Clone, Default)]
+pub struct IfSimplifier {
+    pub signature: ParameterSignature,
+}
+
+impl IfSimplifier {
+    pub fn with_signature(signature: ParameterSignature) -> Self {
+        Self { signature }
+    }
+}
+
+impl NodeTransformer for IfSimplifier {
+    fn transform_if(&mut self, parameter: If<Option<BoxedNode>>) -> Option<BoxedNode> {
+        let (condition, success, failure) = (parameter.condition?, parameter.success?, parameter.failure?);
+
+        if let NodeFrame::BooleanConstant(value) = condition.data {
+            return Some(if value { success } else { failure });
+        }
+        if condition.is_droppable() && success.clone().get_structure_key() == failure.clone().get_structure_key() {
+            return Some(success);
+        }
+
+        let is_bool = matches!(self.signature.type_of(&condition), Ok(Some(ParameterType::Bool)));
+        Some(match (&success.data, &failure.data) {
+            (NodeFrame::BooleanConstant(true), NodeFrame::BooleanConstant(false)) if is_bool => condition,
+            (NodeFrame::BooleanConstant(false), NodeFrame::BooleanConstant(true)) => BoxedNode::unary(UnaryOperation::Not, condition),
+            _ => BoxedNode::if_else(condition, success, failure),
+        })
+    }
+}
+
+#[cfg(test)]
+mod if_simplifier_tests {
+    use super::*;
+    use crate::computing::ComputingDomain;
+    use crate::parser::parse;
+
+    fn simplify(source: &str) -> anyhow::Result<String> {
+        let signature = ParameterSignature::new().with_parameter("p", ComputingDomain::Classical, ParameterType::Bool);
+        let node = IfSimplifier::with_signature(signature).transform_node(parse(source)?).expect("nothing is dropped");
+        Ok(node.to_string())
+    }
+
+    #[test]
+    fn constant_conditions_select_a_branch() -> anyhow::Result<()> {
+        assert_eq!("a + 1", simplify("a + 1 if True else b")?);
+        assert_eq!("b", simplify("a + 1 if False else b")?);
+        // Only booleans are taken as constant conditions
+        assert_eq!("a if 1 else b", simplify("a if 1 else b")?);
+        Ok(())
+    }
+
+    #[test]
+    fn identical_branches_drop_the_condition() -> anyhow::Result<()> {
+        assert_eq!("x * 2", simplify("x * 2 if c == d else x * 2")?);
+        assert_eq!("x * 2 if c else 2 * x", simplify("x * 2 if c else 2 * x")?);
+        Ok(())
+    }
+
+    #[test]
+    fn conditions_that_fail_or_measure_are_kept() -> anyhow::Result<()> {
+        assert_eq!("x if measure(q) == 1 else x", simplify("x if measure(q) == 1 else x")?);
+        assert_eq!("x if 1 / a else x", simplify("x if 1 / a else x")?);
+        assert_eq!("x if c < d else x", simplify("x if c < d else x")?);
+        Ok(())
+    }
+
+    #[test]
+    fn boolean_branches_become_the_condition() -> anyhow::Result<()> {
+        assert_eq!("a < b", simplify("True if a < b else False")?);
+        assert_eq!("p", simplify("True if p else False")?);
+        assert_eq!("True if c else False", simplify("True if c else False")?);
+        assert_eq!("not c", simplify("False if c else True")?);
+        assert_eq!("True if c else 1", simplify("True if c else 1")?);
+        Ok(())
+    }
+
+    #[test]
+    fn nested_ifs_collapse_bottom_up() -> anyhow::Result<()> {
+        assert_eq!("y", simplify("(x if False else y) if c else y")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use crate::graph::boxed_nodes::BoxedNode;
+use crate::graph::node_frame::{FunctionParameter, NodeFrame};
+use crate::graph::node_transformer::NodeTransformer;
+use crate::operations::IntegerSemantics;
+use crate::simplifier::{Simplifier, SimplifierError};
+use crate::simplifier::modules::classical_evaluator::ClassicalEvaluator;
+use crate::simplifier::modules::if_simplifier::IfSimplifier;
+
+// Parameter values known ahead of time, each one a numeric, string, boolean or bitvec constant
+pub type Bindings = BTreeMap<String, NodeFrame<Box<BoxedNode>>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecializationError {
+    NotAConstant { parameter: String },
+    Simplifier(SimplifierError),
+}
+
+impl Display for SpecializationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpecializationError::NotAConstant { parameter } => write!(f, "the value bound to `{parameter}` is not a constant"),
+            SpecializationError::Simplifier(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for SpecializationError {}
+
+impl From<SimplifierError> for SpecializationError {
+    fn from(error: SimplifierError) -> Self {
+        SpecializationError::Simplifier(error)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Specialization {
+    pub node: BoxedNode,
+    // Parameters the specialized graph still uses, bound parameters in dropped branches are gone too
+    pub free_parameters: BTreeSet<String>,
+}
+
+// Replaces bound parameters with their constants, leaving the others untouched
+struct Substitution<'a> {
+    bindings: &'a Bindings,
+}
+
+impl NodeTransformer for Substitution<'_> {
+    fn transform_function_parameter(&mut self, parameter: FunctionParameter) -> Option<BoxedNode> {
+        let data = match self.bindings.get(&parameter.identifier) {
+            Some(value) => value.clone(),
+            None => NodeFrame::FunctionParameter(parameter),
+        };
+        Some(BoxedNode { data })
+    }
+}
+
+pub fn specialize(node: BoxedNode, bindings: &Bindings) -> Result<Specialization, SpecializationError> {
+    specialize_with(node, bindings, IntegerSemantics::default())
+}
+
+// Substitutes the bindings, then folds constants and If nodes until nothing changes
+pub fn specialize_with(node: BoxedNode, bindings: &Bindings, semantics: IntegerSemantics) -> Result<Specialization, SpecializationError> {
+    for (parameter, value) in bindings {
+        if !matches!(value, NodeFrame::NumericConstant(_) | NodeFrame::StringConstant(_) | NodeFrame::BooleanConstant(_) | NodeFrame::BitVec(_)) {
+            return Err(SpecializationError::NotAConstant { parameter: parameter.clone() });
+        }
+    }
+
+    let substituted = Substitution { bindings }
+        .transform_node(node)
+        .expect("substitution never drops a node");
+    let mut simplifier = Simplifier::new()
+        .with_pass("classical_evaluator", ClassicalEvaluator { semantics })
+        .with_pass("if", IfSimplifier::default());
+    let node = simplifier.run_to_fixed_point(substituted)?.node;
+    let free_parameters = node.parameter_names();
+    Ok(Specialization {
+        node,
+        free_parameters,
+    })
+}
+
+#[cfg(test)]
+mod specialization_tests {
+    use super::*;
+    use crate::graph::node_frame::{BitVec, Numeric};
+    use crate::parser::parse;
+
+    fn bindings(values: Vec<(&str, NodeFrame<Box<BoxedNode>>)>) -> Bindings {
+        values.into_iter().map(|(name, value)| (name.to_string(), value)).collect()
+    }
+
+    #[test]
+    fn bound_parameters_are_folded() -> anyhow::Result<()> {
+        let bound = bindings(vec![("n", NodeFrame::NumericConstant(Numeric::Int(4)))]);
+        let specialized = specialize(parse("x * (n * 2 - 1) + n")?, &bound)?;
+        assert_eq!("x * 7 + 4", specialized.node.to_string());
+        assert_eq!(BTreeSet::from(["x".to_string()]), specialized.free_parameters);
+        Ok(())
+    }
+
+    #[test]
+    fn dead_branches_lose_their_parameters() -> anyhow::Result<()> {
+        let bound = bindings(vec![
+            ("n", NodeFrame::NumericConstant(Numeric::Double(0.5))),
+            ("mode", NodeFrame::StringConstant("fast".to_string())),
+        ]);
+        let specialized = specialize(parse("a + n if mode == 'fast' and n < 1 else b")?, &bound)?;
+        assert_eq!("a + 0.5", specialized.node.to_string());
+        assert_eq!(BTreeSet::from(["a".to_string()]), specialized.free_parameters);
+        Ok(())
+    }
+
+    #[test]
+    fn unbound_graphs_are_only_folded() -> anyhow::Result<()> {
+        let bitvec = NodeFrame::BitVec(BitVec { length: 2, bit_string: "01".to_string() });
+        let specialized = specialize(parse("x + (1 + 2)")?, &bindings(vec![("unused", bitvec)]))?;
+        assert_eq!("x + 3", specialized.node.to_string());
+        assert_eq!(BTreeSet::from(["x".to_string()]), specialized.free_parameters);
+        Ok(())
+    }
+
+    #[test]
+    fn bindings_must_be_constants() -> anyhow::Result<()> {
+        let bound = bindings(vec![("n", NodeFrame::FunctionParameter(FunctionParameter { identifier: "m".to_string() }))]);
+        let error = specialize(parse("n + 1")?, &bound).expect_err("n is bound to a parameter");
+        assert_eq!(SpecializationError::NotAConstant { parameter: "n".to_string() }, error);
+        Ok(())
+    }
+}